@@ -9,7 +9,8 @@ pub struct Account {
     pub nonce: u64,
     pub code: Vec<u8>,
     pub code_hash: H256,
-    pub storage: HashMap<U256, U256>,
+    /// Only written through `WorldState`, which journals every change.
+    storage: HashMap<U256, U256>,
 }
 
 impl Account {
@@ -73,7 +74,7 @@ impl Account {
         self.storage.get(key).copied().unwrap_or(U256::zero())
     }
 
-    pub fn set_storage(&mut self, key: U256, value: U256) {
+    fn set_storage(&mut self, key: U256, value: U256) {
         if value == U256::zero() {
             // Remove zero values to save space
             self.storage.remove(&key);
@@ -86,7 +87,7 @@ impl Account {
         &self.storage
     }
 
-    pub fn clear_storage(&mut self) {
+    fn clear_storage(&mut self) {
         self.storage.clear();
     }
 
@@ -146,6 +147,10 @@ impl Default for Account {
 pub struct WorldState {
     pub accounts: HashMap<Address, Account>,
     pub state_root: H256,
+    /// What changes since the last `take_journal` overwrote. Storage must change through
+    /// `WorldState` rather than an `Account` handle for it to be recorded.
+    #[serde(skip)]
+    pub journal: ReverseDiff,
 }
 
 impl WorldState {
@@ -153,6 +158,7 @@ impl WorldState {
         WorldState {
             accounts: HashMap::new(),
             state_root: H256::zero(),
+            journal: ReverseDiff::default(),
         }
     }
 
@@ -161,6 +167,7 @@ impl WorldState {
     }
 
     pub fn get_account_mut(&mut self, address: &Address) -> &mut Account {
+        self.journal_account(address);
         self.accounts.entry(*address).or_insert_with(Account::new)
     }
 
@@ -169,11 +176,15 @@ impl WorldState {
     }
 
     pub fn create_account(&mut self, address: Address, account: Account) {
+        self.journal_account(&address);
+        self.journal_storage(&address);
         self.accounts.insert(address, account);
         self.update_state_root();
     }
 
     pub fn delete_account(&mut self, address: &Address) {
+        self.journal_account(address);
+        self.journal_storage(address);
         self.accounts.remove(address);
         self.update_state_root();
     }
@@ -236,6 +247,7 @@ impl WorldState {
         self.increment_nonce(deployer);
 
         let contract_account = Account::new_contract(U256::zero(), code);
+        self.journal_account(contract_address);
         self.accounts.insert(*contract_address, contract_account);
 
         self.update_state_root();
@@ -271,9 +283,15 @@ impl WorldState {
     }
 
     pub fn set_storage(&mut self, address: &Address, key: U256, value: U256) {
+        self.write_storage(address, key, value);
+        self.update_state_root();
+    }
+
+    /// Like `set_storage`, leaving `state_root` stale for callers that write a batch of slots.
+    pub fn write_storage(&mut self, address: &Address, key: U256, value: U256) {
+        self.journal_slot(address, key);
         let account = self.get_account_mut(address);
         account.set_storage(key, value);
-        self.update_state_root();
     }
 
     pub fn get_all_storage(&self, address: &Address) -> HashMap<U256, U256> {
@@ -283,12 +301,21 @@ impl WorldState {
     }
 
     pub fn clear_storage(&mut self, address: &Address) {
-        if let Some(account) = self.accounts.get_mut(address) {
-            account.clear_storage();
+        if self.wipe_storage(address) {
             self.update_state_root();
         }
     }
 
+    /// Like `clear_storage`, leaving `state_root` stale. Returns whether the account exists.
+    pub fn wipe_storage(&mut self, address: &Address) -> bool {
+        self.journal_storage(address);
+        let Some(account) = self.accounts.get_mut(address) else {
+            return false;
+        };
+        account.clear_storage();
+        true
+    }
+
     pub fn get_all_contracts(&self) -> Vec<Address> {
         self.accounts.iter()
             .filter(|(_, account)| account.is_contract())
@@ -312,6 +339,13 @@ impl WorldState {
     }
 
     pub fn remove_empty_accounts(&mut self) {
+        let empty: Vec<_> = self.accounts.iter()
+            .filter(|(_, account)| account.is_empty())
+            .map(|(address, _)| *address)
+            .collect();
+        for address in &empty {
+            self.journal_account(address);
+        }
         self.accounts.retain(|_, account| !account.is_empty());
         self.update_state_root();
     }
@@ -408,16 +442,114 @@ impl WorldState {
     }
 
     pub fn restore_snapshot(&mut self, snapshot: WorldStateSnapshot) {
+        let addresses: Vec<_> = self.accounts.keys().chain(snapshot.accounts.keys()).copied().collect();
+        for address in &addresses {
+            self.journal_account(address);
+            self.journal_storage(address);
+        }
         self.accounts = snapshot.accounts;
         self.state_root = snapshot.state_root;
     }
 
     pub fn apply_changes(&mut self, other: &WorldState) {
         for (address, account) in &other.accounts {
+            self.journal_account(address);
+            self.journal_storage(address);
             self.accounts.insert(*address, account.clone());
         }
         self.update_state_root();
     }
+
+    /// Records `address`'s balance, nonce and code unless they already changed since the last
+    /// `take_journal`.
+    pub fn journal_account(&mut self, address: &Address) {
+        if self.journal.accounts.contains_key(address) {
+            return;
+        }
+
+        let prior = self.accounts.get(address).map(|account| Account {
+            balance: account.balance,
+            nonce: account.nonce,
+            code: account.code.clone(),
+            code_hash: account.code_hash,
+            storage: HashMap::new(),
+        });
+        self.journal.accounts.insert(*address, prior);
+    }
+
+    /// Records the current value of one storage slot, as `journal_account` does for accounts.
+    pub fn journal_slot(&mut self, address: &Address, key: U256) {
+        let value = self.get_storage(address, &key);
+        self.journal.storage.entry(*address).or_default().entry(key).or_insert(value);
+    }
+
+    /// Records every storage slot of `address`, before its storage is wiped or replaced.
+    pub fn journal_storage(&mut self, address: &Address) {
+        if let Some(account) = self.accounts.get(address) {
+            let journaled = self.journal.storage.entry(*address).or_default();
+            for (key, value) in &account.storage {
+                journaled.entry(*key).or_insert(*value);
+            }
+        }
+    }
+
+    /// Hands out the changes recorded so far and starts a fresh journal.
+    pub fn take_journal(&mut self) -> ReverseDiff {
+        std::mem::take(&mut self.journal)
+    }
+
+    /// Undoes the changes `diff` was recorded for. The journal does not record this, and the
+    /// state root is left for the caller to update.
+    pub fn revert(&mut self, diff: &ReverseDiff) {
+        for (address, prior) in &diff.accounts {
+            match prior {
+                Some(prior) => {
+                    let account = self.accounts.entry(*address).or_default();
+                    account.balance = prior.balance;
+                    account.nonce = prior.nonce;
+                    account.code = prior.code.clone();
+                    account.code_hash = prior.code_hash;
+                }
+                None => {
+                    self.accounts.remove(address);
+                }
+            }
+        }
+
+        for (address, slots) in &diff.storage {
+            // Accounts that did not exist before had no storage either.
+            if let Some(account) = self.accounts.get_mut(address) {
+                for (key, value) in slots {
+                    account.set_storage(*key, *value);
+                }
+            }
+        }
+    }
+}
+
+/// Values that a run of state changes overwrote, enough to undo them.
+#[derive(Debug, Clone, Default)]
+pub struct ReverseDiff {
+    /// Each changed account as it was, without storage; `None` if it did not exist.
+    pub accounts: HashMap<Address, Option<Account>>,
+    /// Previous value of each changed slot, zero if it was unset.
+    pub storage: HashMap<Address, HashMap<U256, U256>>,
+}
+
+impl ReverseDiff {
+    /// Folds in the diff of changes made after this one's. Where both recorded something,
+    /// the older value here is the one to restore.
+    pub fn merge(&mut self, later: ReverseDiff) {
+        for (address, prior) in later.accounts {
+            self.accounts.entry(address).or_insert(prior);
+        }
+        for (address, slots) in later.storage {
+            let storage = self.storage.entry(address).or_default();
+            for (key, value) in slots {
+                storage.entry(key).or_insert(value);
+            }
+        }
+    }
 }
 
 impl Default for WorldState {
@@ -569,6 +701,42 @@ mod tests {
         assert_eq!(state.get_balance(&alice), U256::from(1000));
     }
 
+    #[test]
+    fn test_journal_reverts_changes() {
+        let mut state = WorldState::new();
+        let alice = Address::from([1u8; 20]);
+        let bob = Address::from([2u8; 20]);
+        let contract = Address::from([3u8; 20]);
+
+        state.set_balance(&alice, U256::from(100));
+        state.set_storage(&contract, U256::from(1), U256::from(5));
+        state.take_journal();
+
+        state.set_balance(&alice, U256::from(50));
+        state.increment_nonce(&alice);
+        state.set_storage(&contract, U256::from(1), U256::from(6));
+        state.set_storage(&contract, U256::from(2), U256::from(7));
+        state.delete_account(&contract);
+        state.create_account(bob, Account::new_with_balance(U256::from(1)));
+
+        let diff = state.take_journal();
+        assert_eq!(diff.accounts.len(), 3);
+        assert_eq!(diff.storage[&contract], HashMap::from([(U256::from(1), U256::from(5)), (U256::from(2), U256::zero())]));
+
+        state.revert(&diff);
+        assert_eq!(state.get_balance(&alice), U256::from(100));
+        assert_eq!(state.get_nonce(&alice), 0);
+        assert!(!state.account_exists(&bob));
+        assert_eq!(state.get_all_storage(&contract), HashMap::from([(U256::from(1), U256::from(5))]));
+
+        // Batched writes leave the root stale but are journaled all the same.
+        state.wipe_storage(&contract);
+        state.write_storage(&contract, U256::from(3), U256::from(8));
+        let diff = state.take_journal();
+        state.revert(&diff);
+        assert_eq!(state.get_all_storage(&contract), HashMap::from([(U256::from(1), U256::from(5))]));
+    }
+
     #[test]
     fn test_empty_account_removal() {
         let mut state = WorldState::new();
@@ -0,0 +1,219 @@
+use crate::evm::{EvmLog, StateDiff};
+use ethereum_types::{Address, H256, U256};
+use std::collections::BTreeMap;
+
+/// Topic of `Transfer(address,address,uint256)`, which ERC-20 and ERC-721 share.
+const TRANSFER_TOPIC: [u8; 32] = [
+    0xdd, 0xf2, 0x52, 0xad, 0x1b, 0xe2, 0xc8, 0x9b, 0x69, 0xc2, 0xb0, 0x68, 0xfc, 0x37, 0x8d, 0xaa,
+    0x95, 0x2b, 0xa7, 0xf1, 0x63, 0xc4, 0xa1, 0x16, 0x28, 0xf5, 0x5a, 0x4d, 0xf5, 0x23, 0xb3, 0xef,
+];
+
+/// A token movement announced by a `Transfer` event.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TokenTransfer {
+    Erc20 { token: Address, from: Address, to: Address, value: U256 },
+    Erc721 { token: Address, from: Address, to: Address, token_id: U256 },
+}
+
+/// Decodes a `Transfer` event emitted by `token`. ERC-20 puts the amount in the data; ERC-721
+/// indexes the token id, so its event has a fourth topic and no data.
+pub fn decode_transfer(token: Address, topics: &[H256], data: &[u8]) -> Option<TokenTransfer> {
+    if topics.first()?.as_bytes() != TRANSFER_TOPIC {
+        return None;
+    }
+
+    match topics.len() {
+        3 if data.len() == 32 => Some(TokenTransfer::Erc20 {
+            token,
+            from: topic_address(&topics[1]),
+            to: topic_address(&topics[2]),
+            value: U256::from_big_endian(data),
+        }),
+        4 if data.is_empty() => Some(TokenTransfer::Erc721 {
+            token,
+            from: topic_address(&topics[1]),
+            to: topic_address(&topics[2]),
+            token_id: U256::from_big_endian(topics[3].as_bytes()),
+        }),
+        _ => None,
+    }
+}
+
+fn topic_address(topic: &H256) -> Address {
+    Address::from_slice(&topic.as_bytes()[12..])
+}
+
+/// Net change of an amount. At most one of `gained` and `lost` is non-zero.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Delta {
+    pub gained: U256,
+    pub lost: U256,
+}
+
+impl Delta {
+    pub fn between(before: U256, after: U256) -> Self {
+        if after >= before {
+            Delta { gained: after - before, lost: U256::zero() }
+        } else {
+            Delta { gained: U256::zero(), lost: before - after }
+        }
+    }
+
+    pub fn is_zero(&self) -> bool {
+        self.gained.is_zero() && self.lost.is_zero()
+    }
+
+    fn credit(&mut self, amount: U256) {
+        if self.lost >= amount {
+            self.lost -= amount;
+        } else {
+            self.gained += amount - self.lost;
+            self.lost = U256::zero();
+        }
+    }
+
+    fn debit(&mut self, amount: U256) {
+        if self.gained >= amount {
+            self.gained -= amount;
+        } else {
+            self.lost += amount - self.gained;
+            self.gained = U256::zero();
+        }
+    }
+}
+
+/// ERC-721 token ids an address ended up receiving and giving away.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NftDelta {
+    pub received: Vec<U256>,
+    pub sent: Vec<U256>,
+}
+
+/// What an execution did to everyone's assets, as a wallet would summarise it before signing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AssetChanges {
+    /// Native balance changes, gas payments and the miner tip included.
+    pub native: BTreeMap<Address, Delta>,
+    /// ERC-20 balance changes, keyed by (token, holder).
+    pub erc20: BTreeMap<(Address, Address), Delta>,
+    /// ERC-721 ownership changes, keyed by (token, holder).
+    pub erc721: BTreeMap<(Address, Address), NftDelta>,
+}
+
+impl AssetChanges {
+    /// Native deltas come from `state_diff`, token deltas from the `Transfer` events in `logs`.
+    pub fn new(state_diff: &StateDiff, logs: &[EvmLog]) -> Self {
+        let transfers = logs.iter().filter_map(|log| decode_transfer(log.address, &log.topics, &log.data));
+        AssetChanges { native: native_deltas(state_diff), ..Self::from_transfers(transfers) }
+    }
+
+    /// Token deltas alone, without native ones. Mints and burns leave out the zero address on
+    /// the other side.
+    pub fn from_transfers(transfers: impl IntoIterator<Item = TokenTransfer>) -> Self {
+        let mut changes = AssetChanges::default();
+        for transfer in transfers {
+            match transfer {
+                TokenTransfer::Erc20 { token, from, to, value } => {
+                    if !from.is_zero() {
+                        changes.erc20.entry((token, from)).or_default().debit(value);
+                    }
+                    if !to.is_zero() {
+                        changes.erc20.entry((token, to)).or_default().credit(value);
+                    }
+                }
+                TokenTransfer::Erc721 { token, from, to, token_id } => {
+                    if !from.is_zero() {
+                        let delta = changes.erc721.entry((token, from)).or_default();
+                        move_token(&mut delta.received, &mut delta.sent, token_id);
+                    }
+                    if !to.is_zero() {
+                        let delta = changes.erc721.entry((token, to)).or_default();
+                        move_token(&mut delta.sent, &mut delta.received, token_id);
+                    }
+                }
+            }
+        }
+
+        changes.erc20.retain(|_, delta| !delta.is_zero());
+        changes.erc721.retain(|_, delta| !delta.received.is_empty() || !delta.sent.is_empty());
+        changes
+    }
+}
+
+/// Native balance changes in `state_diff`, gas payments and the miner tip included.
+pub fn native_deltas(state_diff: &StateDiff) -> BTreeMap<Address, Delta> {
+    state_diff.accounts.iter()
+        .map(|(address, diff)| (*address, Delta::between(diff.pre.balance, diff.post.balance)))
+        .filter(|(_, delta)| !delta.is_zero())
+        .collect()
+}
+
+/// Moves `token_id` towards `to`: a token that went the other way earlier cancels out.
+fn move_token(from: &mut Vec<U256>, to: &mut Vec<U256>, token_id: U256) {
+    match from.iter().position(|id| *id == token_id) {
+        Some(index) => {
+            from.remove(index);
+        }
+        None => to.push(token_id),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::{AccountDiff, AccountState};
+
+    fn decode(log: &EvmLog) -> Option<TokenTransfer> {
+        decode_transfer(log.address, &log.topics, &log.data)
+    }
+
+    fn transfer_log(token: Address, from: Address, to: Address, extra_topics: Vec<H256>, data: Vec<u8>) -> EvmLog {
+        let mut topics = vec![H256(TRANSFER_TOPIC), H256::from(from), H256::from(to)];
+        topics.extend(extra_topics);
+        EvmLog { address: token, topics, data }
+    }
+
+    #[test]
+    fn test_decode_transfers() {
+        let token = Address::repeat_byte(0x70);
+        let (alice, bob) = (Address::repeat_byte(0xa1), Address::repeat_byte(0xb0));
+
+        let erc20 = transfer_log(token, alice, bob, vec![], H256::from_low_u64_be(10).as_bytes().to_vec());
+        assert_eq!(decode(&erc20), Some(TokenTransfer::Erc20 { token, from: alice, to: bob, value: U256::from(10) }));
+
+        let erc721 = transfer_log(token, alice, bob, vec![H256::from_low_u64_be(3)], vec![]);
+        assert_eq!(decode(&erc721), Some(TokenTransfer::Erc721 { token, from: alice, to: bob, token_id: U256::from(3) }));
+
+        let other = EvmLog { address: token, topics: vec![H256::repeat_byte(1)], data: vec![] };
+        assert_eq!(decode(&other), None);
+    }
+
+    #[test]
+    fn test_asset_changes_net_out_transfers() {
+        let (usd, nft) = (Address::repeat_byte(0x70), Address::repeat_byte(0x71));
+        let (alice, bob) = (Address::repeat_byte(0xa1), Address::repeat_byte(0xb0));
+        let amount = |value: u64| H256::from_low_u64_be(value).as_bytes().to_vec();
+        let id = |value: u64| vec![H256::from_low_u64_be(value)];
+
+        let logs = vec![
+            transfer_log(usd, Address::zero(), alice, vec![], amount(100)),
+            transfer_log(usd, alice, bob, vec![], amount(30)),
+            transfer_log(usd, bob, alice, vec![], amount(30)),
+            transfer_log(usd, alice, bob, vec![], amount(10)),
+            transfer_log(nft, bob, alice, id(1), vec![]),
+            transfer_log(nft, alice, bob, id(2), vec![]),
+            transfer_log(nft, alice, bob, id(1), vec![]),
+        ];
+        let mut state_diff = StateDiff::default();
+        let balance = |balance: u64| AccountState { balance: U256::from(balance), ..Default::default() };
+        state_diff.accounts.insert(alice, AccountDiff { pre: balance(50), post: balance(20) });
+        state_diff.accounts.insert(bob, AccountDiff { pre: balance(5), post: balance(5) });
+
+        let changes = AssetChanges::new(&state_diff, &logs);
+        assert_eq!(changes.native, BTreeMap::from([(alice, Delta { gained: U256::zero(), lost: U256::from(30) })]));
+        assert_eq!(changes.erc20[&(usd, alice)], Delta { gained: U256::from(90), lost: U256::zero() });
+        assert_eq!(changes.erc20[&(usd, bob)], Delta { gained: U256::from(10), lost: U256::zero() });
+        assert_eq!(changes.erc721[&(nft, alice)], NftDelta { received: vec![], sent: vec![U256::from(2)] });
+        assert_eq!(changes.erc721[&(nft, bob)], NftDelta { received: vec![U256::from(2)], sent: vec![] });
+    }
+}
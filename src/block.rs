@@ -1,6 +1,14 @@
-use crate::transaction::Transaction;
-use ethereum_types::{H256,U256};
+use crate::transaction::{EnvelopeType, Transaction};
+use crate::trie::{ordered_trie_root, EMPTY_TRIE_ROOT};
+use ethereum_types::{Address, Bloom, H256, U256};
+use rlp::RlpStream;
 use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+
+/// Base fee of the genesis block; later blocks derive theirs per EIP-1559.
+pub const INITIAL_BASE_FEE: u64 = 1_000_000_000; // 1 gwei
+const ELASTICITY_MULTIPLIER: u64 = 2;
+const BASE_FEE_MAX_CHANGE_DENOMINATOR: u64 = 8;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Block {
@@ -11,7 +19,18 @@ pub struct Block {
     pub timestamp: u64,
     pub gas_limit: u64,
     pub gas_used: u64,
-    pub nonce: u64
+    pub nonce: u64,
+    /// Receives transaction fees; the block reward arrives through the coinbase transaction.
+    pub miner: Address,
+    /// Burned for every unit of gas used in this block.
+    pub base_fee_per_gas: U256,
+    /// Leading zero hex digits the proof of work was mined for.
+    pub difficulty: u64,
+    // Commitments filled in by executing the block, before it is hashed.
+    pub state_root: H256,
+    pub transactions_root: H256,
+    pub receipts_root: H256,
+    pub logs_bloom: Bloom,
 }
 
 impl Block {
@@ -28,13 +47,18 @@ impl Block {
             timestamp: std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap().as_secs(),
             gas_limit: 30_000_000,
             gas_used: 0,
-            nonce: 0
+            nonce: 0,
+            miner: Address::zero(),
+            base_fee_per_gas: U256::zero(),
+            difficulty: 0,
+            state_root: H256::zero(),
+            transactions_root: H256::zero(),
+            receipts_root: H256::zero(),
+            logs_bloom: Bloom::zero(),
         }
     }
 
     pub fn calculate_hash(&self) -> H256 {
-        use sha3::{Digest, Keccak256};
-
         let mut hasher = Keccak256::new();
         hasher.update(&self.number.to_be_bytes());
         hasher.update(self.parent_hash.as_bytes());
@@ -42,12 +66,14 @@ impl Block {
         hasher.update(&self.nonce.to_be_bytes());
         hasher.update(&self.gas_limit.to_be_bytes());
         hasher.update(&self.gas_used.to_be_bytes());
-
-        for tx in &self.transactions {
-            if let Some(tx_hash) = tx.hash {
-                hasher.update(tx_hash.as_bytes());
-            }
-        }
+        hasher.update(self.miner.as_bytes());
+        let mut base_fee = [0u8; 32];
+        self.base_fee_per_gas.to_big_endian(&mut base_fee);
+        hasher.update(base_fee);
+        hasher.update(self.state_root.as_bytes());
+        hasher.update(self.transactions_root.as_bytes());
+        hasher.update(self.receipts_root.as_bytes());
+        hasher.update(self.logs_bloom.as_bytes());
 
         H256::from_slice(&hasher.finalize())
     }
@@ -56,6 +82,75 @@ impl Block {
         self.hash = Some(self.calculate_hash());
     }
 
+    /// Value of the PREVRANDAO opcode inside this block. The parent's proof-of-work hash stands
+    /// in for beacon chain randomness; it is also reported as the header's `mixHash`.
+    pub fn prev_randao(&self) -> H256 {
+        self.parent_hash
+    }
+
+    /// Base fee of the child block: it moves by up to 1/8 towards keeping blocks half full.
+    pub fn next_base_fee(&self) -> U256 {
+        let gas_target = self.gas_limit / ELASTICITY_MULTIPLIER;
+        let base_fee = self.base_fee_per_gas;
+
+        if gas_target == 0 || self.gas_used == gas_target {
+            return base_fee;
+        }
+
+        if self.gas_used > gas_target {
+            let delta = base_fee * (self.gas_used - gas_target) / gas_target / BASE_FEE_MAX_CHANGE_DENOMINATOR;
+            base_fee + delta.max(U256::one())
+        } else {
+            let delta = base_fee * (gas_target - self.gas_used) / gas_target / BASE_FEE_MAX_CHANGE_DENOMINATOR;
+            base_fee - delta
+        }
+    }
+
+    /// Trie root over the encoded transactions, keyed by their index in the block.
+    pub fn calculate_transactions_root(&self) -> H256 {
+        ordered_trie_root(self.transactions.iter().map(|tx| tx.encode()))
+    }
+
+    pub fn commitments(&self) -> ExecutionCommitments {
+        ExecutionCommitments {
+            gas_used: self.gas_used,
+            state_root: self.state_root,
+            transactions_root: self.transactions_root,
+            receipts_root: self.receipts_root,
+            logs_bloom: self.logs_bloom,
+        }
+    }
+
+    /// Size in bytes of the RLP-encoded header and transaction list.
+    pub fn size(&self) -> usize {
+        let mut stream = RlpStream::new_list(2);
+        stream.begin_list(13);
+        stream.append(&self.parent_hash);
+        stream.append(&self.miner);
+        stream.append(&self.state_root);
+        stream.append(&self.transactions_root);
+        stream.append(&self.receipts_root);
+        stream.append(&self.logs_bloom);
+        stream.append(&self.difficulty);
+        stream.append(&self.number);
+        stream.append(&self.gas_limit);
+        stream.append(&self.gas_used);
+        stream.append(&self.timestamp);
+        stream.append(&self.nonce);
+        stream.append(&self.base_fee_per_gas);
+
+        stream.begin_list(self.transactions.len());
+        for tx in &self.transactions {
+            let encoded = tx.encode();
+            match tx.envelope_type {
+                EnvelopeType::Legacy => stream.append_raw(&encoded, 1),
+                // Typed envelopes are embedded as byte strings.
+                _ => stream.append(&encoded),
+            };
+        }
+        stream.out().len()
+    }
+
     // mining logic
 
     pub fn mine(&mut self, difficulty: usize) -> u64 {
@@ -77,6 +172,7 @@ impl Block {
 
             if hash_str.starts_with(&target) {
                 self.hash = Some(hash);
+                self.difficulty = difficulty as u64;
                 let duration = start_time.elapsed();
                 println!("Block mined! Nonce: {}, Hash: {}, Time: {:?}, Attempts: {}",
                          self.nonce, hash_str, duration, attempts);
@@ -107,6 +203,9 @@ impl Block {
             H256::zero(),
             Vec::new(),
         );
+        genesis.base_fee_per_gas = U256::from(INITIAL_BASE_FEE);
+        genesis.transactions_root = EMPTY_TRIE_ROOT;
+        genesis.receipts_root = EMPTY_TRIE_ROOT;
 
         println!("Mining genesis block...");
         genesis.mine(2);
@@ -126,11 +225,46 @@ impl Block {
 
 }
 
+/// Header fields that commit to the outcome of executing a block.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExecutionCommitments {
+    pub gas_used: u64,
+    pub state_root: H256,
+    pub transactions_root: H256,
+    pub receipts_root: H256,
+    pub logs_bloom: Bloom,
+}
+
+impl ExecutionCommitments {
+    /// Checks the commitments a block arrived with against the ones executing it produced.
+    pub fn verify(&self, executed: &ExecutionCommitments) -> Result<(), String> {
+        if self.gas_used != executed.gas_used {
+            return Err(format!("Invalid gas used. Expected {}, got {}", executed.gas_used, self.gas_used));
+        }
+        if self.state_root != executed.state_root {
+            return Err(format!("Invalid state root. Expected {:?}, got {:?}", executed.state_root, self.state_root));
+        }
+        if self.transactions_root != executed.transactions_root {
+            return Err(format!(
+                "Invalid transactions root. Expected {:?}, got {:?}",
+                executed.transactions_root,
+                self.transactions_root
+            ));
+        }
+        if self.receipts_root != executed.receipts_root {
+            return Err(format!("Invalid receipts root. Expected {:?}, got {:?}", executed.receipts_root, self.receipts_root));
+        }
+        if self.logs_bloom != executed.logs_bloom {
+            return Err("Invalid logs bloom".to_string());
+        }
+        Ok(())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::transaction::Transaction;
-    use ethereum_types::Address;
 
     #[test]
     fn test_genesis_block() {
@@ -167,4 +301,45 @@ mod tests {
 
         println!("Block with transaction: {:?}", block);
     }
+
+    #[test]
+    fn test_next_base_fee_follows_gas_usage() {
+        let mut parent = Block::new(1, H256::zero(), vec![]);
+        parent.base_fee_per_gas = U256::from(INITIAL_BASE_FEE);
+
+        parent.gas_used = parent.gas_limit / 2;
+        assert_eq!(parent.next_base_fee(), U256::from(INITIAL_BASE_FEE));
+
+        parent.gas_used = parent.gas_limit;
+        assert_eq!(parent.next_base_fee(), U256::from(1_125_000_000u64));
+
+        parent.gas_used = 0;
+        assert_eq!(parent.next_base_fee(), U256::from(875_000_000u64));
+
+        // Even a tiny overshoot raises the fee by at least one wei.
+        parent.base_fee_per_gas = U256::from(7);
+        parent.gas_used = parent.gas_limit / 2 + 1;
+        assert_eq!(parent.next_base_fee(), U256::from(8));
+    }
+
+    #[test]
+    fn test_transactions_root_and_size_track_contents() {
+        let from = Address::from([1u8; 20]);
+        let to = Address::from([2u8; 20]);
+
+        let mut first = Transaction::new_transfer(from, to, U256::from(1000), 0);
+        first.sign(&[1u8; 32], 1337).unwrap();
+        let mut second = Transaction::new_transfer(from, to, U256::from(2000), 1);
+        second.sign(&[1u8; 32], 1337).unwrap();
+
+        let empty = Block::new(1, H256::zero(), vec![]);
+        let one = Block::new(1, H256::zero(), vec![first.clone()]);
+        let two = Block::new(1, H256::zero(), vec![first.clone(), second.clone()]);
+        let reordered = Block::new(1, H256::zero(), vec![second, first]);
+
+        assert_eq!(empty.calculate_transactions_root(), EMPTY_TRIE_ROOT);
+        assert_ne!(two.calculate_transactions_root(), reordered.calculate_transactions_root());
+        assert!(empty.size() < one.size());
+        assert!(one.size() < two.size());
+    }
 }
\ No newline at end of file
@@ -1,14 +1,65 @@
 use crate::block::Block;
-use crate::transaction::{Transaction, TransactionType};
-use crate::account::WorldState;
-use crate::evm::{RevmExecutor, ContractExecutionResult, ContractUtils};
-use ethereum_types::{H256, Address, U256};
+use crate::transaction::{address_from_secret_key, Transaction, TransactionType};
+use crate::account::{ReverseDiff, WorldState};
+use crate::assets::{decode_transfer, native_deltas, AssetChanges, Delta};
+use crate::hardfork::{ForkSchedule, Hardfork};
+use crate::evm::{intrinsic_gas, nonce_and_has_code, AccountOverride, CallOverrides, CallRequest, ChainDatabase, InternalOperation, InternalOperationTracer, RevmExecutor, ContractExecutionResult, ContractUtils, StateDiff, Trace};
+use crate::receipt::{receipts_root, TransactionReceipt};
+use crate::revert::ErrorRegistry;
+use ethereum_types::{Bloom, H160, H256, Address, U256};
+use revm::db::CacheDB;
+use revm::primitives::SpecId;
+use revm::inspectors::NoOpInspector;
+use revm::{Database, DatabaseCommit, DatabaseRef, Inspector};
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::fmt::Debug;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Number of recent post-block states kept for calls against older blocks.
+pub const STATE_HISTORY_LIMIT: usize = 128;
+/// Tip suggested while recent blocks carry no fee-paying transactions.
+pub const DEFAULT_PRIORITY_FEE: u64 = 1_000_000_000; // 1 gwei
+const PRIORITY_FEE_SAMPLE_BLOCKS: usize = 20;
+const MAX_FEE_HISTORY_BLOCKS: u64 = 1024;
+/// Most blocks a single `simulate` request may produce, as in geth.
+pub const MAX_SIMULATED_BLOCKS: usize = 256;
+/// Time between consecutive simulated blocks unless overridden.
+const SIMULATED_BLOCK_TIME: u64 = 12;
+
+/// EIP-2935 history contract, which serves block hashes beyond BLOCKHASH's 256-block window.
+pub const HISTORY_STORAGE_ADDRESS: Address = H160([
+    0x00, 0x00, 0xf9, 0x08, 0x27, 0xf1, 0xc5, 0x3a, 0x10, 0xcb,
+    0x7a, 0x02, 0x33, 0x5b, 0x17, 0x53, 0x20, 0x00, 0x29, 0x35,
+]);
+const HISTORY_SERVE_WINDOW: u64 = 8191;
+/// Read side of the history contract: given a 32-byte block number within the window, returns
+/// that block's hash, otherwise reverts. The node writes the ring buffer itself.
+const HISTORY_STORAGE_CODE: &str = "3660201415602a5760003580431115602a57804303611fff10602a57611fff90065460005260206000f35b60006000fd";
 
 #[derive(Debug, Clone)]
 pub struct Blockchain {
     pub blocks: Vec<Block>,
     pub state: WorldState,
     pub chain_id: u64,
+    pub receipts: HashMap<H256, TransactionReceipt>,
+    /// Transaction hash -> (block number, index within the block).
+    pub transaction_locations: HashMap<H256, (u64, usize)>,
+    /// Address -> hashes of the mined transactions that sent to or from it, directly or
+    /// through an internal operation, in chain order.
+    pub address_transactions: HashMap<Address, Vec<H256>>,
+    /// Reverse diffs of the last `STATE_HISTORY_LIMIT` blocks, keyed by block number. Undoing
+    /// one, together with everything applied outside blocks since, gives back the state the
+    /// block executed on top of; older states are rebuilt from `state` that way.
+    pub state_history: VecDeque<(u64, ReverseDiff)>,
+    /// Base fees destroyed so far (EIP-1559).
+    pub burned_fees: U256,
+    /// Whether parent hashes are recorded in the EIP-2935 history contract. Always the case
+    /// once Prague is active.
+    pub block_hash_history: bool,
+    pub fork_schedule: ForkSchedule,
+    /// Custom errors named in revert reasons of receipts and failed calls.
+    pub custom_errors: ErrorRegistry,
 }
 
 impl Blockchain {
@@ -20,6 +71,14 @@ impl Blockchain {
             blocks: vec![genesis],
             state: WorldState::new(),
             chain_id: 1337, // Custom chain ID
+            receipts: HashMap::new(),
+            transaction_locations: HashMap::new(),
+            address_transactions: HashMap::new(),
+            state_history: VecDeque::new(),
+            burned_fees: U256::zero(),
+            block_hash_history: false,
+            fork_schedule: ForkSchedule::new(),
+            custom_errors: ErrorRegistry::new(),
         }
     }
 
@@ -29,6 +88,21 @@ impl Blockchain {
         blockchain
     }
 
+    pub fn new_with_fork_schedule(chain_id: u64, fork_schedule: ForkSchedule) -> Self {
+        let mut blockchain = Self::new_with_chain_id(chain_id);
+        blockchain.fork_schedule = fork_schedule;
+        blockchain
+    }
+
+    pub fn hardfork_at(&self, block: &Block) -> Hardfork {
+        self.fork_schedule.hardfork_at(block.number, block.timestamp)
+    }
+
+    /// The revm rules `block` executes under.
+    pub fn spec_at(&self, block: &Block) -> SpecId {
+        self.hardfork_at(block).spec_id()
+    }
+
     pub fn get_latest_block(&self) -> &Block {
         self.blocks.last().unwrap()
     }
@@ -56,31 +130,183 @@ impl Blockchain {
             return Err("Invalid parent hash".to_string());
         }
 
-        if block.hash.is_some() && !block.is_valid_proof(1) {
-            return Err("Invalid proof of work".to_string());
+        // Blocks assembled without a base fee get the one their parent dictates. A hashed block
+        // already committed to its base fee, so it has to carry the right one.
+        let expected_base_fee = self.get_latest_block().next_base_fee();
+        if block.base_fee_per_gas.is_zero() && block.hash.is_none() {
+            block.base_fee_per_gas = expected_base_fee;
+        } else if block.base_fee_per_gas != expected_base_fee {
+            return Err(format!("Invalid base fee. Expected {}, got {}", expected_base_fee, block.base_fee_per_gas));
         }
 
-        let mut total_gas_used = 0u64;
-        for tx in &block.transactions {
-            if let Some(result) = self.execute_transaction(tx)? {
-                total_gas_used += result.gas_used;
-            } else {
-                total_gas_used += 21000;
+        if let Some(hash) = block.hash {
+            if hash != block.calculate_hash() {
+                return Err("Block hash does not match its header".to_string());
+            }
+            if !block.is_valid_proof(1) {
+                return Err("Invalid proof of work".to_string());
             }
         }
 
-        block.gas_used = total_gas_used;
+        // Changes made outside blocks since the parent are undone along with it, so states
+        // rebuilt from the history include them from the parent on.
+        let outside_blocks = self.state.take_journal();
+        if let Some((_, parent_diff)) = self.state_history.back_mut() {
+            parent_diff.merge(outside_blocks);
+        }
+
+        // The journal now records only this block, so a rejected block can be undone without
+        // leaving partial state behind. Hashed blocks must commit to exactly what executing
+        // them produced; the rest are hashed once their commitments are filled in.
+        let provided = block.commitments();
+        let receipts = match self.execute_block(&mut block) {
+            Ok(receipts) if block.hash.is_some() => provided.verify(&block.commitments()).map(|()| receipts),
+            result => result,
+        };
+        let receipts = match receipts {
+            Ok(receipts) => receipts,
+            Err(e) => {
+                let changes = self.state.take_journal();
+                self.state.revert(&changes);
+                self.state.update_state_root();
+                println!("Rejected block {}: {}", block.number, e);
+                return Err(e);
+            }
+        };
+
         if block.hash.is_none() {
             block.set_hash();
         }
 
+        let block_hash = block.hash.unwrap();
+        for mut receipt in receipts {
+            receipt.block_hash = block_hash;
+            self.transaction_locations.insert(receipt.transaction_hash, (block.number, receipt.transaction_index as usize));
+            self.index_addresses(&receipt);
+            self.receipts.insert(receipt.transaction_hash, receipt);
+        }
+
+        self.burned_fees += U256::from(block.gas_used) * block.base_fee_per_gas;
+
+        if self.state_history.len() == STATE_HISTORY_LIMIT {
+            self.state_history.pop_front();
+        }
+        self.state_history.push_back((block.number, self.state.take_journal()));
+
         println!("⛓Added block {} with hash {:?}", block.number, block.hash);
         self.blocks.push(block);
 
         Ok(())
     }
 
-    fn execute_transaction(&mut self, tx: &Transaction) -> Result<Option<ContractExecutionResult>, String> {
+    /// Executes `block` on top of the head without keeping the result and fills in the header
+    /// fields that commit to it: gas used, roots and logs bloom, plus the base fee if unset.
+    /// Blocks need these before they are mined.
+    pub fn prepare_block(&mut self, block: &mut Block) -> Result<(), String> {
+        if block.base_fee_per_gas.is_zero() {
+            block.base_fee_per_gas = self.get_latest_block().next_base_fee();
+        }
+
+        let outside_blocks = self.state.take_journal();
+        let result = self.execute_block(block);
+        let changes = std::mem::replace(&mut self.state.journal, outside_blocks);
+        self.state.revert(&changes);
+        self.state.update_state_root();
+        result.map(|_| ())
+    }
+
+    /// Runs `block` against the state and sets its commitments to what that produced.
+    fn execute_block(&mut self, block: &mut Block) -> Result<Vec<TransactionReceipt>, String> {
+        let receipts = self.apply_block_transactions(block)?;
+
+        self.state.update_state_root();
+        block.state_root = self.state.get_state_root();
+        block.transactions_root = block.calculate_transactions_root();
+        block.receipts_root = receipts_root(&receipts);
+        block.logs_bloom = Bloom::zero();
+        for receipt in &receipts {
+            block.logs_bloom.accrue_bloom(&receipt.logs_bloom);
+        }
+        Ok(receipts)
+    }
+
+    /// Installs the EIP-2935 history contract; every later block records its parent's hash there.
+    pub fn enable_block_hash_history(&mut self) -> Result<(), String> {
+        install_history_contract(&mut self.state)?;
+        self.state.update_state_root();
+
+        self.block_hash_history = true;
+        Ok(())
+    }
+
+    /// Whether `block` records its parent hash in the EIP-2935 history contract.
+    fn records_block_hashes(&self, block: &Block) -> bool {
+        self.block_hash_history || self.fork_schedule.is_active(Hardfork::Prague, block.number, block.timestamp)
+    }
+
+    fn apply_block_transactions(&mut self, block: &mut Block) -> Result<Vec<TransactionReceipt>, String> {
+        if self.records_block_hashes(block) {
+            record_parent_hash(&mut self.state, block)?;
+        }
+
+        let base_fee = block.base_fee_per_gas;
+        let mut receipts = Vec::with_capacity(block.transactions.len());
+        let mut cumulative_gas_used = 0u64;
+        let mut log_count = 0u64;
+
+        for index in 0..block.transactions.len() {
+            // Earlier transactions in the block may have deployed code at the target.
+            let tx_type = self.classify_transaction(&block.transactions[index]);
+            block.transactions[index].tx_type = tx_type;
+
+            let tx = &block.transactions[index];
+            let (result, internal_operations) = self.execute_transaction(tx, block)?.unzip();
+            // Only the coinbase reward executes outside the EVM, and it uses no gas.
+            let gas_used = result.as_ref().map_or(0, |result| result.gas_used);
+            cumulative_gas_used += gas_used;
+
+            let mut receipt = TransactionReceipt::new(
+                tx,
+                index as u64,
+                block.number,
+                result.as_ref(),
+                gas_used,
+                cumulative_gas_used,
+                tx.effective_gas_price(base_fee),
+                log_count,
+            );
+            receipt.internal_operations = internal_operations.unwrap_or_default();
+            if let Some(result) = result.as_ref().filter(|result| result.is_revert()) {
+                receipt.revert_reason = self.custom_errors.decode(&result.return_data);
+            }
+            log_count += receipt.logs.len() as u64;
+            receipts.push(receipt);
+        }
+
+        block.gas_used = cumulative_gas_used;
+        block.validate_gas_usage()?;
+        Ok(receipts)
+    }
+
+    /// Looks up a mined transaction together with its block and position in that block.
+    pub fn get_transaction_by_hash(&self, hash: &H256) -> Option<(&Transaction, &Block, usize)> {
+        let (block_number, index) = *self.transaction_locations.get(hash)?;
+        let block = self.get_block_by_number(block_number)?;
+        Some((block.transactions.get(index)?, block, index))
+    }
+
+    pub fn get_transaction_receipt(&self, hash: &H256) -> Option<&TransactionReceipt> {
+        self.receipts.get(hash)
+    }
+
+    /// Applies `tx` in the context of `block`. Senders pay gas_used × effective gas price; the
+    /// base fee portion is burned and the remaining tip goes to the block's miner. Returns the
+    /// execution with the internal operations it made, or `None` for the coinbase reward.
+    fn execute_transaction(
+        &mut self,
+        tx: &Transaction,
+        block: &Block,
+    ) -> Result<Option<(ContractExecutionResult, Vec<InternalOperation>)>, String> {
         if tx.from == Address::zero() {
             if let Some(to) = tx.to {
                 let account = self.state.get_account_mut(&to);
@@ -92,8 +318,9 @@ impl Blockchain {
 
         tx.validate()?;
 
-        if tx.is_contract_deployment() || tx.is_contract_call() {
-            return self.execute_with_revm(tx);
+        let sender = tx.recover_sender(self.chain_id)?;
+        if sender != tx.from {
+            return Err(format!("Signature does not match sender. Expected {}, recovered {}", tx.from, sender));
         }
 
         let expected_nonce = self.state.get_nonce(&tx.from);
@@ -101,60 +328,48 @@ impl Blockchain {
             return Err(format!("Invalid nonce. Expected {}, got {}", expected_nonce, tx.nonce));
         }
 
-        let total_cost = tx.value + tx.estimated_gas_cost();
-        if self.state.get_balance(&tx.from) < total_cost {
-            return Err("Insufficient balance for transaction and gas".to_string());
+        let base_fee = block.base_fee_per_gas;
+        let effective_gas_price = tx.effective_gas_price(base_fee);
+        if effective_gas_price < base_fee {
+            return Err(format!("Gas price {} is below the base fee {}", effective_gas_price, base_fee));
         }
 
-        if let Some(to) = tx.to {
-            self.state.transfer(&tx.from, &to, tx.value)?;
-
-            let sender = self.state.get_account_mut(&tx.from);
-            sender.nonce += 1;
-            sender.balance -= tx.estimated_gas_cost(); // Deduct gas cost
-
-            println!("💸 Transfer: {} -> {} ({} wei)", tx.from, to, tx.value);
-        }
-
-        Ok(None)
+        let spec_id = self.spec_at(block);
+        let db = ChainDatabase::new(&mut self.state, &self.blocks);
+        let revm = RevmExecutor::new_with_inspector(db, block, self.chain_id, spec_id, InternalOperationTracer::new());
+        Self::execute_with_revm(revm, tx, true, &self.custom_errors).map(Some)
     }
 
-    fn execute_with_revm(&mut self, tx: &Transaction) -> Result<Option<ContractExecutionResult>, String> {
-        let latest_block = self.get_latest_block();
-        let mut revm = RevmExecutor::new(
-            latest_block.number + 1,
-            latest_block.timestamp,
-            Address::from([0u8; 20]), // Coinbase address
-            50_000_000, // 50M gas limit per block
-        );
-
-        revm.load_state_from_world(&self.state)?;
-
-        let result = revm.execute_transaction(
-            tx.from,
-            tx.to,
-            tx.value,
-            tx.data.clone(),
-            tx.gas_limit,
-            tx.gas_price,
-            tx.nonce,
-        )?;
-
-        revm.save_state_to_world(&mut self.state)?;
+    fn classify_transaction(&self, tx: &Transaction) -> TransactionType {
+        let target_has_code = tx.to.is_some_and(|to| !self.state.get_contract_code(&to).is_empty());
+        TransactionType::classify(tx.to, &tx.data, target_has_code)
+    }
 
-        let sender_account = self.state.get_account_mut(&tx.from);
-        sender_account.nonce += 1;
+    /// Commits `tx` through `revm`, returning the execution with the internal operations it
+    /// made. Without `validate` the nonce and base fee go unchecked, as in `simulate`.
+    fn execute_with_revm<DB>(
+        mut revm: RevmExecutor<'_, DB, InternalOperationTracer>,
+        tx: &Transaction,
+        validate: bool,
+        custom_errors: &ErrorRegistry,
+    ) -> Result<(ContractExecutionResult, Vec<InternalOperation>), String>
+    where
+        DB: Database + DatabaseCommit,
+        DB::Error: Debug,
+    {
+        // revm charges the sender, refunds unused gas and pays the tip to the coinbase.
+        let result = if validate {
+            revm.execute_transaction(tx)?
+        } else {
+            revm.execute_unvalidated_transaction(tx)?
+        };
+        let internal_operations = revm.into_inspector().into_operations();
 
         if result.success {
             match tx.tx_type {
                 TransactionType::ContractDeployment => {
                     if let Some(addr) = result.contract_address {
                         println!("Contract deployed at: {}", addr);
-
-                        let contract_account = self.state.get_account_mut(&addr);
-                        if !result.return_data.is_empty() {
-                            contract_account.set_code(result.return_data.clone());
-                        }
                     }
                 }
                 TransactionType::ContractCall => {
@@ -163,26 +378,94 @@ impl Blockchain {
                         println!("Return data: {} bytes", result.return_data.len());
                     }
                 }
-                _ => {}
+                TransactionType::Transfer => {
+                    println!("💸 Transfer: {} -> {:?} ({} wei)", tx.from, tx.to, tx.value);
+                }
             }
         } else {
             println!("REVM transaction failed: {}", result.reason);
-            if let Some(error) = &result.error {
+            if result.is_revert()
+                && let Some(reason) = custom_errors.decode(&result.return_data)
+            {
+                println!("Revert reason: {}", reason);
+            } else if let Some(error) = &result.error {
                 println!("Error details: {}", error);
             }
         }
 
-        Ok(Some(result))
+        Ok((result, internal_operations))
+    }
+
+    /// Checks `tx` before it joins `pending`, the transactions waiting ahead of it for the next
+    /// block: its signature, nonce, intrinsic gas, fee cap against the next base fee and
+    /// whether the sender can pay for it after them. Errors read like geth's.
+    pub fn validate_pending_transaction(&self, tx: &Transaction, pending: &[Transaction]) -> Result<(), String> {
+        tx.validate()?;
+        if tx.recover_sender(self.chain_id)? != tx.from {
+            return Err("invalid sender".to_string());
+        }
+
+        let queued: Vec<&Transaction> = pending.iter().filter(|queued| queued.from == tx.from).collect();
+        let expected_nonce = self.state.get_nonce(&tx.from) + queued.len() as u64;
+        if tx.nonce < expected_nonce {
+            return Err(format!("nonce too low: address {:?}, tx: {} state: {}", tx.from, tx.nonce, expected_nonce));
+        }
+        if tx.nonce > expected_nonce {
+            return Err(format!("nonce too high: address {:?}, tx: {} state: {}", tx.from, tx.nonce, expected_nonce));
+        }
+
+        let block = self.pending_block();
+        if tx.gas_limit > block.gas_limit {
+            return Err("exceeds block gas limit".to_string());
+        }
+        let intrinsic = intrinsic_gas(tx, self.spec_at(&block));
+        if tx.gas_limit < intrinsic {
+            return Err(format!("intrinsic gas too low: have {}, want {}", tx.gas_limit, intrinsic));
+        }
+
+        let fee_cap = tx.max_fee_per_gas.unwrap_or(tx.gas_price);
+        if fee_cap < block.base_fee_per_gas {
+            return Err(format!(
+                "max fee per gas less than block base fee: address {:?}, maxFeePerGas: {}, baseFee: {}",
+                tx.from,
+                fee_cap,
+                block.base_fee_per_gas,
+            ));
+        }
+
+        let max_cost = |tx: &Transaction| U256::from(tx.gas_limit) * tx.max_fee_per_gas.unwrap_or(tx.gas_price) + tx.value;
+        let balance = queued.iter().fold(self.state.get_balance(&tx.from), |balance, queued| {
+            balance.saturating_sub(max_cost(queued))
+        });
+        if balance < max_cost(tx) {
+            return Err(format!(
+                "insufficient funds for gas * price + value: address {:?} have {} want {}",
+                tx.from,
+                balance,
+                max_cost(tx),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Header for transactions executed directly against the state rather than in a mined block.
+    fn pending_block(&self) -> Block {
+        let latest = self.get_latest_block();
+        let mut block = Block::new(latest.number + 1, latest.hash.unwrap_or_default(), vec![]);
+        block.base_fee_per_gas = latest.next_base_fee();
+        block
     }
 
     pub fn deploy_contract_with_revm(
         &mut self,
-        deployer: Address,
+        deployer_key: &[u8],
         bytecode: Vec<u8>,
         constructor_args: Vec<u8>,
         value: U256,
         gas_limit: u64,
     ) -> Result<(Address, ContractExecutionResult), String> {
+        let deployer = address_from_secret_key(deployer_key)?;
         let nonce = self.state.get_nonce(&deployer);
 
         let contract_address = ContractUtils::calculate_create_address(&deployer, nonce);
@@ -191,13 +474,14 @@ impl Blockchain {
         deployment_data.extend_from_slice(&constructor_args);
 
         let mut tx = Transaction::new_contract_deployment(deployer, deployment_data, value, nonce);
-        tx.set_hash();
+        tx.gas_limit = gas_limit;
+        tx.sign(deployer_key, self.chain_id)?;
 
-        if let Some(result) = self.execute_transaction(&tx)? {
+        if let Some(result) = self.execute_outside_block(&tx)? {
             if result.success {
                 return Ok((contract_address, result));
             } else {
-                return Err(format!("Contract deployment failed: {}", result.reason));
+                return Err(format!("Contract deployment failed: {}", result.error.unwrap_or(result.reason)));
             }
         }
 
@@ -206,39 +490,353 @@ impl Blockchain {
 
     pub fn call_contract_with_revm(
         &mut self,
-        caller: Address,
+        caller_key: &[u8],
         contract: Address,
         calldata: Vec<u8>,
         value: U256,
         gas_limit: u64,
     ) -> Result<ContractExecutionResult, String> {
+        let caller = address_from_secret_key(caller_key)?;
         let nonce = self.state.get_nonce(&caller);
 
         let mut tx = Transaction::new_contract_call(caller, contract, calldata, value, nonce);
-        tx.set_hash();
+        tx.gas_limit = gas_limit;
+        tx.sign(caller_key, self.chain_id)?;
 
-        if let Some(result) = self.execute_transaction(&tx)? {
+        if let Some(result) = self.execute_outside_block(&tx)? {
             return Ok(result);
         }
 
         Err("Failed to execute contract call".to_string())
     }
 
+    /// Applies `tx` straight to the state on top of the pending block. Its base fee is burned
+    /// just as if it had been mined, so the total supply still adds up.
+    fn execute_outside_block(&mut self, tx: &Transaction) -> Result<Option<ContractExecutionResult>, String> {
+        let block = self.pending_block();
+        let result = self.execute_transaction(tx, &block)?.map(|(result, _)| result);
+        if let Some(result) = &result {
+            self.burned_fees += U256::from(result.gas_used) * block.base_fee_per_gas;
+        }
+        Ok(result)
+    }
+
+    /// World state as of the end of `block_number`. Older states are rebuilt by undoing later
+    /// blocks on a copy of the head state.
+    pub fn state_at(&self, block_number: u64) -> Result<Cow<'_, WorldState>, String> {
+        let latest = self.get_latest_block().number;
+        if block_number == latest {
+            return Ok(Cow::Borrowed(&self.state));
+        }
+
+        if block_number > latest || block_number < self.oldest_state() {
+            return Err(format!("State for block {} is not available", block_number));
+        }
+
+        let mut state = self.state.clone();
+        let outside_blocks = state.take_journal();
+        state.revert(&outside_blocks);
+        for (_, diff) in self.state_history.iter().rev().take_while(|(number, _)| *number > block_number) {
+            state.revert(diff);
+        }
+        state.update_state_root();
+        Ok(Cow::Owned(state))
+    }
+
+    /// Oldest block whose state `state_at` can still rebuild.
+    fn oldest_state(&self) -> u64 {
+        let latest = self.get_latest_block().number;
+        self.state_history.front().map_or(latest, |(number, _)| number - 1)
+    }
+
+    /// Executes a message call on top of the state at `block_number` without changing anything.
+    pub fn call(&self, request: &CallRequest, block_number: u64) -> Result<ContractExecutionResult, String> {
+        self.call_with_overrides(request, block_number, &CallOverrides::default())
+    }
+
+    /// Like `call`, with `overrides` layered over the state and block.
+    pub fn call_with_overrides(
+        &self,
+        request: &CallRequest,
+        block_number: u64,
+        overrides: &CallOverrides,
+    ) -> Result<ContractExecutionResult, String> {
+        let block = self.call_block(block_number, overrides)?;
+        let state = self.state_at(block_number)?;
+        self.call_executor(overrides.overlay(&state), &block, block_number).call(request)
+    }
+
+    /// Finds the lowest gas limit at which `request` succeeds, binary searching up to the
+    /// requested gas or the block gas limit. Returns that limit with the execution it produced;
+    /// if the call fails even at the cap, the cap is returned with the failed execution.
+    pub fn estimate_gas(&self, request: &CallRequest, block_number: u64) -> Result<(u64, ContractExecutionResult), String> {
+        self.estimate_gas_with_overrides(request, block_number, &CallOverrides::default())
+    }
+
+    /// Like `estimate_gas`, with `overrides` layered over the state and block.
+    pub fn estimate_gas_with_overrides(
+        &self,
+        request: &CallRequest,
+        block_number: u64,
+        overrides: &CallOverrides,
+    ) -> Result<(u64, ContractExecutionResult), String> {
+        let block = self.call_block(block_number, overrides)?;
+        let state = self.state_at(block_number)?;
+        let mut revm = self.call_executor(overrides.overlay(&state), &block, block_number);
+        let cap = request.gas.map_or(block.gas_limit, |gas| gas.min(block.gas_limit));
+
+        let mut probe = request.clone();
+        probe.gas = Some(cap);
+        let mut best = revm.call(&probe)?;
+        if !best.success {
+            return Ok((cap, best));
+        }
+
+        // Anything below the gas actually consumed fails; refunds only lower the reported usage.
+        let mut low = best.gas_used.saturating_sub(1);
+        let mut high = cap;
+        while low + 1 < high {
+            let mid = low + (high - low) / 2;
+            probe.gas = Some(mid);
+            match revm.call(&probe) {
+                Ok(result) if result.success => {
+                    high = mid;
+                    best = result;
+                }
+                _ => low = mid,
+            }
+        }
+
+        Ok((high, best))
+    }
+
+    /// Runs `request` like `call` with `inspector` attached.
+    pub fn trace_call<I>(&self, request: &CallRequest, block_number: u64, inspector: I) -> Result<Trace<I>, String>
+    where
+        I: for<'s> Inspector<ChainDatabase<'s, &'s WorldState>>,
+    {
+        let block = self.get_block_by_number(block_number)
+            .ok_or_else(|| format!("Unknown block {}", block_number))?;
+        let state = self.state_at(block_number)?;
+
+        let db = ChainDatabase::new(state.as_ref(), &self.blocks[..block_number as usize]);
+        let mut revm = RevmExecutor::new_with_inspector(db, block, self.chain_id, self.spec_at(block), inspector);
+        let (result, changes) = revm.call_with_state(request)?;
+        Ok(Trace { result, state_diff: StateDiff::new(&state, &changes), inspector: revm.into_inspector() })
+    }
+
+    /// Re-executes the mined transaction `hash` with `inspector` attached, on top of its
+    /// parent's state and the transactions before it in the same block.
+    pub fn trace_transaction<I>(&self, hash: &H256, inspector: I) -> Result<Trace<I>, String>
+    where
+        I: for<'s> Inspector<ChainDatabase<'s, &'s WorldState>>,
+    {
+        let (tx, block, index) = self.get_transaction_by_hash(hash)
+            .ok_or_else(|| format!("Transaction {:?} not found", hash))?;
+        if tx.from == Address::zero() {
+            return Err(format!("Transaction {:?} is a block reward and does not run in the EVM", hash));
+        }
+
+        let state = self.replay_block_prefix(block, index)?;
+        let db = ChainDatabase::new(&state, &self.blocks[..block.number as usize]);
+        let mut revm = RevmExecutor::new_with_inspector(db, block, self.chain_id, self.spec_at(block), inspector);
+        let (result, changes) = revm.replay_transaction(tx)?;
+        Ok(Trace { result, state_diff: StateDiff::new(&state, &changes), inspector: revm.into_inspector() })
+    }
+
+    /// Assets `request` would move if it ran on top of the state at `block_number`.
+    pub fn simulate_asset_changes(
+        &self,
+        request: &CallRequest,
+        block_number: u64,
+    ) -> Result<(ContractExecutionResult, AssetChanges), String> {
+        let trace = self.trace_call(request, block_number, NoOpInspector)?;
+        let changes = AssetChanges::new(&trace.state_diff, &trace.result.logs);
+        Ok((trace.result, changes))
+    }
+
+    /// Token movements of the mined transaction `hash`, decoded from the `Transfer` events in
+    /// its receipt, so every mined transaction has them. `native` is left empty.
+    pub fn transaction_token_changes(&self, hash: &H256) -> Result<(&TransactionReceipt, AssetChanges), String> {
+        let receipt = self.get_transaction_receipt(hash)
+            .ok_or_else(|| format!("Transaction {:?} not found", hash))?;
+        let transfers = receipt.logs.iter().filter_map(|log| decode_transfer(log.address, &log.topics, &log.data));
+        Ok((receipt, AssetChanges::from_transfers(transfers)))
+    }
+
+    /// Native balance changes of the mined transaction `hash`. Receipts do not record them, so
+    /// the transaction is replayed, which needs its parent's state to still be in the history.
+    pub fn transaction_native_changes(&self, hash: &H256) -> Result<BTreeMap<Address, Delta>, String> {
+        let (tx, block, _) = self.get_transaction_by_hash(hash)
+            .ok_or_else(|| format!("Transaction {:?} not found", hash))?;
+        // Block rewards are plain balance credits, as in `execute_transaction`.
+        if tx.from == Address::zero() {
+            return Ok(tx.to.into_iter().map(|to| (to, Delta::between(U256::zero(), tx.value))).collect());
+        }
+        if block.number - 1 < self.oldest_state() {
+            return Err(format!(
+                "state pruned: native balance changes of {:?} need the state of block {}, which is no longer kept",
+                hash,
+                block.number - 1,
+            ));
+        }
+
+        let trace = self.trace_transaction(hash, NoOpInspector)?;
+        Ok(native_deltas(&trace.state_diff))
+    }
+
+    /// Executes `blocks` one after another on top of the state at `block_number`, committing
+    /// each call to an overlay of the state so later calls see earlier ones. Without
+    /// `validation`, as in geth, the base fee defaults to zero, calls default to a zero gas
+    /// price, which waives the base fee, and nonces go unchecked.
+    pub fn simulate(
+        &self,
+        block_number: u64,
+        blocks: &[SimulatedBlockCalls],
+        validation: bool,
+    ) -> Result<Vec<SimulatedBlock>, String> {
+        if blocks.len() > MAX_SIMULATED_BLOCKS {
+            return Err(format!("too many blocks: at most {} can be simulated", MAX_SIMULATED_BLOCKS));
+        }
+        let base = self.get_block_by_number(block_number)
+            .ok_or_else(|| format!("Unknown block {}", block_number))?;
+
+        let base_state = self.state_at(block_number)?;
+        let mut state = CacheDB::new(base_state.as_ref());
+        let chain = &self.blocks[..=block_number as usize];
+        // Lets BLOCKHASH resolve the simulated blocks on top of the chain.
+        let mut simulated_hashes = HashMap::new();
+        let mut simulated: Vec<SimulatedBlock> = Vec::with_capacity(blocks.len());
+
+        for calls in blocks {
+            let parent = simulated.last().map_or(base, |simulated| &simulated.block);
+            let mut block = Block::new(parent.number + 1, parent.hash.unwrap_or_default(), vec![]);
+            block.timestamp = parent.timestamp + SIMULATED_BLOCK_TIME;
+            block.miner = parent.miner;
+            block.base_fee_per_gas = if validation { parent.next_base_fee() } else { U256::zero() };
+            calls.overrides.apply_to_block(&mut block);
+            if block.number <= parent.number {
+                return Err(format!("block number {} is not after {}", block.number, parent.number));
+            }
+            if block.timestamp <= parent.timestamp {
+                return Err(format!("block timestamp {} is not after {}", block.timestamp, parent.timestamp));
+            }
+
+            calls.overrides.apply_to_overlay(&mut state);
+            if self.records_block_hashes(&block) {
+                parent_hash_overrides(&block)?.apply_to_overlay(&mut state);
+            }
+
+            let spec_id = self.spec_at(&block);
+            let mut results = Vec::with_capacity(calls.calls.len());
+            for (index, call) in calls.calls.iter().enumerate() {
+                let tx = self.simulated_transaction(call, &block, &state, validation)?;
+                let db = ChainDatabase::new_with_simulated_hashes(&mut state, chain, &simulated_hashes);
+                let revm = RevmExecutor::new_with_inspector(db, &block, self.chain_id, spec_id, InternalOperationTracer::new());
+                let (result, _) = Self::execute_with_revm(revm, &tx, validation, &self.custom_errors)
+                    .map_err(|e| format!("call {} in block {}: {}", index, block.number, e))?;
+
+                block.gas_used += result.gas_used;
+                block.transactions.push(tx);
+                results.push(result);
+            }
+
+            block.transactions_root = block.calculate_transactions_root();
+            block.set_hash();
+            simulated_hashes.insert(block.number, block.hash.unwrap_or_default());
+            simulated.push(SimulatedBlock { block, results });
+        }
+
+        Ok(simulated)
+    }
+
+    /// Unsigned transaction standing in for `call` inside a simulated block. It gets the
+    /// sender's next nonce, the gas left in the block and, with `validation`, the block's base
+    /// fee as gas price unless the call sets them.
+    fn simulated_transaction(
+        &self,
+        call: &CallRequest,
+        block: &Block,
+        state: &CacheDB<&WorldState>,
+        validation: bool,
+    ) -> Result<Transaction, String> {
+        let gas_left = block.gas_limit - block.gas_used;
+        let gas_limit = call.gas.unwrap_or(gas_left);
+        if gas_limit > gas_left {
+            return Err(format!("block gas limit reached: {} needed, {} left", gas_limit, gas_left));
+        }
+
+        let from = call.from.unwrap_or_default();
+        let (nonce, _) = nonce_and_has_code(state, &from);
+        let target_has_code = call.to.is_some_and(|to| nonce_and_has_code(state, &to).1);
+        let default_gas_price = if validation { block.base_fee_per_gas } else { U256::zero() };
+        let mut tx = Transaction::new_with_gas(
+            from,
+            call.to,
+            call.value,
+            call.data.clone(),
+            gas_limit,
+            call.gas_price.unwrap_or(default_gas_price),
+            call.nonce.unwrap_or(nonce),
+            TransactionType::classify(call.to, &call.data, target_has_code),
+        );
+        tx.chain_id = Some(self.chain_id);
+        tx.set_hash();
+        Ok(tx)
+    }
+
+    /// World state right before `block.transactions[index]` executed.
+    fn replay_block_prefix(&self, block: &Block, index: usize) -> Result<WorldState, String> {
+        let mut state = self.state_at(block.number - 1)?.into_owned();
+        if self.records_block_hashes(block) {
+            record_parent_hash(&mut state, block)?;
+        }
+
+        let blocks = &self.blocks[..block.number as usize];
+        for tx in &block.transactions[..index] {
+            // Block rewards are plain balance credits, as in `execute_transaction`.
+            if tx.from == Address::zero() {
+                if let Some(to) = tx.to {
+                    state.get_account_mut(&to).balance += tx.value;
+                }
+                continue;
+            }
+
+            let db = ChainDatabase::new(&mut state, blocks);
+            RevmExecutor::new(db, block, self.chain_id, self.spec_at(block)).execute_transaction(tx)?;
+        }
+        Ok(state)
+    }
+
+    /// Block a call at `block_number` runs in, with the block overrides applied.
+    fn call_block(&self, block_number: u64, overrides: &CallOverrides) -> Result<Block, String> {
+        let mut block = self.get_block_by_number(block_number)
+            .ok_or_else(|| format!("Unknown block {}", block_number))?
+            .clone();
+        overrides.apply_to_block(&mut block);
+        Ok(block)
+    }
+
+    /// Executor for calls on top of `state`, with the hashes of blocks up to `block_number`.
+    fn call_executor<'s, S: DatabaseRef<Error = Infallible>>(
+        &'s self,
+        state: S,
+        block: &Block,
+        block_number: u64,
+    ) -> RevmExecutor<'s, ChainDatabase<'s, S>> {
+        let db = ChainDatabase::new(state, &self.blocks[..block_number as usize]);
+        RevmExecutor::new(db, block, self.chain_id, self.spec_at(block))
+    }
+
     pub fn view_contract_call(
         &self,
         caller: Address,
         contract: Address,
         calldata: Vec<u8>,
     ) -> Result<Vec<u8>, String> {
-        let latest_block = self.get_latest_block();
-        let mut revm = RevmExecutor::new(
-            latest_block.number + 1,
-            latest_block.timestamp,
-            Address::from([0u8; 20]),
-            50_000_000,
-        );
-
-        revm.load_state_from_world(&self.state)?;
+        let db = ChainDatabase::new(&self.state, &self.blocks);
+        let block = self.pending_block();
+        let mut revm = RevmExecutor::new(db, &block, self.chain_id, self.spec_at(&block));
 
         let return_data = revm.view_call(caller, contract, calldata)?;
         Ok(return_data)
@@ -276,33 +874,101 @@ impl Blockchain {
         Ok(())
     }
 
-    pub fn get_total_supply(&self) -> u64 {
-        let mut total = 0;
-        for block in &self.blocks {
-            if block.number > 0 && !block.transactions.is_empty() {
-                let coinbase = &block.transactions[0];
-                if coinbase.from == Address::zero() {
-                    total += coinbase.value.as_u64();
-                }
-            }
+    /// Median tip paid over the last few blocks, for `eth_maxPriorityFeePerGas`.
+    pub fn suggest_priority_fee(&self) -> U256 {
+        let mut tips: Vec<U256> = self.blocks.iter().rev()
+            .take(PRIORITY_FEE_SAMPLE_BLOCKS)
+            .flat_map(|block| self.block_tips(block))
+            .map(|(tip, _)| tip)
+            .collect();
+
+        if tips.is_empty() {
+            return U256::from(DEFAULT_PRIORITY_FEE);
         }
-        total
+        tips.sort();
+        tips[tips.len() / 2]
+    }
+
+    /// Base fees, gas usage and tip percentiles for up to `block_count` blocks ending at
+    /// `newest_block`, shaped like `eth_feeHistory`.
+    pub fn fee_history(&self, block_count: u64, newest_block: u64, reward_percentiles: &[f64]) -> FeeHistory {
+        let newest_block = newest_block.min(self.get_latest_block().number);
+        let block_count = block_count.clamp(1, MAX_FEE_HISTORY_BLOCKS);
+        let oldest_block = (newest_block + 1).saturating_sub(block_count);
+
+        let mut history = FeeHistory {
+            oldest_block,
+            base_fee_per_gas: Vec::new(),
+            gas_used_ratio: Vec::new(),
+            reward: Vec::new(),
+        };
+
+        for block in &self.blocks[oldest_block as usize..=newest_block as usize] {
+            history.base_fee_per_gas.push(block.base_fee_per_gas);
+            history.gas_used_ratio.push(block.gas_used as f64 / block.gas_limit as f64);
+
+            let tips = self.block_tips(block);
+            history.reward.push(reward_percentiles.iter().map(|percentile| percentile_tip(&tips, *percentile)).collect());
+        }
+        // The list includes the base fee of the block after the newest one.
+        history.base_fee_per_gas.push(self.blocks[newest_block as usize].next_base_fee());
+
+        history
+    }
+
+    /// Tips (effective gas price above the base fee) paid in `block` and the gas each
+    /// transaction used, cheapest first.
+    fn block_tips(&self, block: &Block) -> Vec<(U256, u64)> {
+        let mut tips: Vec<_> = block.transactions.iter()
+            .filter_map(|tx| self.receipts.get(&tx.hash?))
+            .filter(|receipt| receipt.gas_used > 0)
+            .map(|receipt| (receipt.effective_gas_price.saturating_sub(block.base_fee_per_gas), receipt.gas_used))
+            .collect();
+        tips.sort();
+        tips
+    }
+
+    /// Ether currently in existence. Burned base fees have left every balance, so this is
+    /// everything minted or pre-funded minus `burned_fees`.
+    pub fn get_total_supply(&self) -> U256 {
+        self.state.accounts.values().fold(U256::zero(), |total, account| total + account.balance)
+    }
+
+    pub fn get_total_burned(&self) -> U256 {
+        self.burned_fees
     }
 
     pub fn get_transaction_count(&self, address: &Address) -> u64 {
         self.state.get_nonce(address)
     }
 
+    /// Transactions sent from or to `address`, including those whose contract code moved value
+    /// to or from it or created it.
     pub fn get_transactions_for_address(&self, address: &Address) -> Vec<&Transaction> {
-        let mut transactions = Vec::new();
-        for block in &self.blocks {
-            for tx in &block.transactions {
-                if tx.from == *address || tx.to == Some(*address) {
-                    transactions.push(tx);
-                }
-            }
+        self.address_transactions.get(address).into_iter().flatten()
+            .filter_map(|hash| self.get_transaction_by_hash(hash))
+            .map(|(tx, _, _)| tx)
+            .collect()
+    }
+
+    /// Records the transaction of `receipt` under its sender, recipient and the endpoints of
+    /// its internal operations.
+    fn index_addresses(&mut self, receipt: &TransactionReceipt) {
+        let mut addresses: Vec<Address> = receipt.internal_operations.iter()
+            .flat_map(|op| [op.from, op.to])
+            .chain([receipt.from])
+            .chain(receipt.to)
+            .collect();
+        addresses.sort();
+        addresses.dedup();
+        for address in addresses {
+            self.address_transactions.entry(address).or_default().push(receipt.transaction_hash);
         }
-        transactions
+    }
+
+    /// Internal operations the mined transaction `hash` made, in execution order.
+    pub fn get_internal_operations(&self, hash: &H256) -> Option<&[InternalOperation]> {
+        self.get_transaction_receipt(hash).map(|receipt| receipt.internal_operations.as_slice())
     }
 
     pub fn print_chain_info(&self) {
@@ -312,6 +978,7 @@ impl Blockchain {
         println!("Latest block: {}", self.get_latest_block().number);
         println!("Latest hash: {:?}", self.get_latest_block().hash);
         println!("Total supply: {} wei", self.get_total_supply());
+        println!("Burned fees: {} wei", self.get_total_burned());
 
         println!("\n=== BLOCKS ===");
         for block in &self.blocks {
@@ -348,25 +1015,102 @@ impl Blockchain {
             transaction_count: total_transactions,
             total_gas_used,
             total_supply: self.get_total_supply(),
+            total_burned: self.get_total_burned(),
             contract_count,
             chain_id: self.chain_id,
         }
     }
 }
 
+fn install_history_contract(state: &mut WorldState) -> Result<(), String> {
+    let account = state.get_account_mut(&HISTORY_STORAGE_ADDRESS);
+    if account.code.is_empty() {
+        account.set_code(hex::decode(HISTORY_STORAGE_CODE).map_err(|e| e.to_string())?);
+        account.nonce = 1;
+    }
+    Ok(())
+}
+
+/// EIP-2935 system update at the start of `block`: stores the parent hash in the ring buffer.
+fn record_parent_hash(state: &mut WorldState, block: &Block) -> Result<(), String> {
+    install_history_contract(state)?;
+    if block.number > 0 {
+        let slot = U256::from((block.number - 1) % HISTORY_SERVE_WINDOW);
+        state.set_storage(&HISTORY_STORAGE_ADDRESS, slot, U256::from_big_endian(block.parent_hash.as_bytes()));
+    }
+    Ok(())
+}
+
+/// `record_parent_hash` as overrides, for simulated states layered over the chain's. The
+/// contract's code and nonce never change once installed, so setting them again is harmless.
+fn parent_hash_overrides(block: &Block) -> Result<CallOverrides, String> {
+    let mut history = AccountOverride {
+        nonce: Some(1),
+        code: Some(hex::decode(HISTORY_STORAGE_CODE).map_err(|e| e.to_string())?),
+        ..Default::default()
+    };
+    if block.number > 0 {
+        let slot = U256::from((block.number - 1) % HISTORY_SERVE_WINDOW);
+        history.state_diff.insert(slot, U256::from_big_endian(block.parent_hash.as_bytes()));
+    }
+    Ok(CallOverrides { accounts: HashMap::from([(HISTORY_STORAGE_ADDRESS, history)]), ..Default::default() })
+}
+
 #[derive(Debug, Clone)]
 pub struct BlockchainStats {
     pub block_count: usize,
     pub transaction_count: usize,
     pub total_gas_used: u64,
-    pub total_supply: u64,
+    pub total_supply: U256,
+    pub total_burned: U256,
     pub contract_count: usize,
     pub chain_id: u64,
 }
 
+#[derive(Debug, Clone)]
+pub struct FeeHistory {
+    pub oldest_block: u64,
+    pub base_fee_per_gas: Vec<U256>,
+    pub gas_used_ratio: Vec<f64>,
+    pub reward: Vec<Vec<U256>>,
+}
+
+/// One block of a `simulate` request: overrides applied before it runs, then its calls in order.
+#[derive(Debug, Clone, Default)]
+pub struct SimulatedBlockCalls {
+    pub overrides: CallOverrides,
+    pub calls: Vec<CallRequest>,
+}
+
+/// A block produced by `simulate`, with the result of each of its calls.
+#[derive(Debug, Clone)]
+pub struct SimulatedBlock {
+    pub block: Block,
+    pub results: Vec<ContractExecutionResult>,
+}
+
+/// Smallest tip such that transactions paying at most that much used `percentile`% of the
+/// block's gas, as geth computes fee history rewards.
+fn percentile_tip(tips: &[(U256, u64)], percentile: f64) -> U256 {
+    let total_gas: u64 = tips.iter().map(|(_, gas)| gas).sum();
+    let threshold = (total_gas as f64 * percentile / 100.0) as u64;
+
+    let mut cumulative_gas = 0;
+    for (tip, gas) in tips {
+        cumulative_gas += gas;
+        if cumulative_gas >= threshold {
+            return *tip;
+        }
+    }
+    tips.last().map(|(tip, _)| *tip).unwrap_or_default()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::hardfork::ForkCondition;
+    use crate::assets::Delta;
+    use crate::evm::{AccountOverride, InternalOperationKind, StructLogger, StructLoggerConfig};
 
     #[test]
     fn test_blockchain_creation() {
@@ -376,15 +1120,19 @@ mod tests {
         assert_eq!(blockchain.chain_id, 1337);
     }
 
+    const ONE_ETH: u64 = 1_000_000_000_000_000_000;
+    const ALICE_KEY: [u8; 32] = [1u8; 32];
+
     #[test]
     fn test_add_valid_block() {
         let mut blockchain = Blockchain::new();
 
-        let alice = Address::from([1u8; 20]);
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
         let bob = Address::from([2u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
 
         let mut tx = Transaction::new_transfer(alice, bob, U256::from(100), 0);
-        tx.set_hash();
+        tx.sign(&ALICE_KEY, 1337).unwrap();
 
         let block = Block::new(
             1,
@@ -397,6 +1145,37 @@ mod tests {
         assert_eq!(blockchain.get_block_count(), 2);
     }
 
+    #[test]
+    fn test_pending_transactions_are_checked_before_pooling() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let bob = Address::from([2u8; 20]);
+        // Enough for exactly two transfers at the default gas price.
+        let cost = U256::from(21000) * U256::from(20_000_000_000u64) + U256::from(100);
+        blockchain.state.set_balance(&alice, cost * 2);
+
+        let transfer = |nonce: u64, gas_limit: u64, gas_price: u64| {
+            let mut tx = Transaction::new_transfer(alice, bob, U256::from(100), nonce);
+            tx.gas_limit = gas_limit;
+            tx.gas_price = U256::from(gas_price);
+            tx.sign(&ALICE_KEY, 1337).unwrap();
+            tx
+        };
+
+        let first = transfer(0, 21000, 20_000_000_000);
+        assert!(blockchain.validate_pending_transaction(&first, &[]).is_ok());
+        let pending = vec![first.clone()];
+
+        let error = |tx: &Transaction| blockchain.validate_pending_transaction(tx, &pending).unwrap_err();
+        assert!(error(&first).starts_with("nonce too low"));
+        assert!(error(&transfer(2, 21000, 20_000_000_000)).starts_with("nonce too high"));
+        assert!(error(&transfer(1, 20000, 20_000_000_000)).starts_with("intrinsic gas too low"));
+        assert!(error(&transfer(1, 21000, 1)).starts_with("max fee per gas less than block base fee"));
+        assert!(error(&transfer(1, 21001, 20_000_000_000)).starts_with("insufficient funds"));
+        assert!(blockchain.validate_pending_transaction(&transfer(1, 21000, 20_000_000_000), &pending).is_ok());
+    }
+
     #[test]
     fn test_invalid_parent_hash() {
         let mut blockchain = Blockchain::new();
@@ -412,22 +1191,759 @@ mod tests {
         assert!(result.unwrap_err().contains("Invalid parent hash"));
     }
 
+    #[test]
+    fn test_rejected_block_leaves_state_untouched() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let bob = Address::from([2u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+        let root_before = blockchain.state.get_state_root();
+
+        let mut valid = Transaction::new_transfer(alice, bob, U256::from(100), 0);
+        valid.sign(&ALICE_KEY, 1337).unwrap();
+        let mut bad_nonce = Transaction::new_transfer(alice, bob, U256::from(100), 5);
+        bad_nonce.sign(&ALICE_KEY, 1337).unwrap();
+
+        let block = Block::new(
+            1,
+            blockchain.get_latest_block().hash.unwrap(),
+            vec![valid, bad_nonce],
+        );
+
+        let result = blockchain.add_block(block);
+        assert!(result.unwrap_err().contains("Invalid nonce"));
+        assert_eq!(blockchain.get_block_count(), 1);
+        assert_eq!(blockchain.state.get_balance(&alice), U256::from(ONE_ETH));
+        assert_eq!(blockchain.state.get_balance(&bob), U256::zero());
+        assert_eq!(blockchain.state.get_nonce(&alice), 0);
+        assert_eq!(blockchain.state.get_state_root(), root_before);
+    }
+
+    #[test]
+    fn test_rejects_forged_sender() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let bob = Address::from([2u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        // Mallory signs a transaction that claims to spend Alice's funds.
+        let mut forged = Transaction::new_transfer(alice, bob, U256::from(100), 0);
+        forged.sign(&[9u8; 32], 1337).unwrap();
+
+        let mut unsigned = Transaction::new_transfer(alice, bob, U256::from(100), 0);
+        unsigned.set_hash();
+
+        for tx in [forged, unsigned] {
+            let block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![tx]);
+            assert!(blockchain.add_block(block).is_err());
+        }
+        assert_eq!(blockchain.state.get_balance(&alice), U256::from(ONE_ETH));
+    }
+
+    #[test]
+    fn test_receipts_are_indexed_by_transaction_hash() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let bob = Address::from([2u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        let mut transfer = Transaction::new_transfer(alice, bob, U256::from(100), 0);
+        transfer.sign(&ALICE_KEY, 1337).unwrap();
+
+        // Emits LOG1(topic 7) with the word 42 as data, then stops without deploying code.
+        let initcode = hex::decode("602a600052600760206000a100").unwrap();
+        let mut deploy = Transaction::new_contract_deployment(alice, initcode, U256::zero(), 1);
+        deploy.sign(&ALICE_KEY, 1337).unwrap();
+
+        let block = Block::new(
+            1,
+            blockchain.get_latest_block().hash.unwrap(),
+            vec![transfer.clone(), deploy.clone()],
+        );
+        blockchain.add_block(block).unwrap();
+        let block_hash = blockchain.get_latest_block().hash.unwrap();
+
+        let transfer_receipt = blockchain.get_transaction_receipt(&transfer.hash.unwrap()).unwrap();
+        assert!(transfer_receipt.status);
+        assert_eq!(transfer_receipt.transaction_index, 0);
+        assert_eq!(transfer_receipt.gas_used, 21000);
+        assert_eq!(transfer_receipt.block_hash, block_hash);
+        assert!(transfer_receipt.logs.is_empty());
+
+        let deploy_receipt = blockchain.get_transaction_receipt(&deploy.hash.unwrap()).unwrap();
+        assert!(deploy_receipt.status);
+        assert_eq!(deploy_receipt.transaction_index, 1);
+        assert_eq!(deploy_receipt.cumulative_gas_used, 21000 + deploy_receipt.gas_used);
+        assert_eq!(deploy_receipt.cumulative_gas_used, blockchain.get_latest_block().gas_used);
+        assert_eq!(deploy_receipt.contract_address, Some(ContractUtils::calculate_create_address(&alice, 1)));
+        assert_eq!(deploy_receipt.logs.len(), 1);
+        assert_eq!(deploy_receipt.logs[0].topics, vec![H256::from_low_u64_be(7)]);
+        assert_eq!(deploy_receipt.logs[0].data, {
+            let mut word = [0u8; 32];
+            U256::from(42).to_big_endian(&mut word);
+            word.to_vec()
+        });
+        assert_eq!(deploy_receipt.logs[0].log_index, 0);
+        assert_eq!(deploy_receipt.effective_gas_price, deploy.gas_price);
+
+        let block = blockchain.get_latest_block();
+        assert_eq!(block.state_root, blockchain.state.get_state_root());
+        assert_eq!(block.transactions_root, block.calculate_transactions_root());
+        assert_ne!(block.receipts_root, H256::zero());
+        assert!(block.logs_bloom.contains_bloom(&deploy_receipt.logs_bloom));
+    }
+
+    #[test]
+    fn test_fees_are_charged_for_gas_used_and_tipped_to_miner() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let bob = Address::from([2u8; 20]);
+        let miner = Address::from([99u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        let mut transfer = Transaction::new_transfer(alice, bob, U256::from(100), 0);
+        transfer.gas_limit = 50_000; // Unused gas must be refunded.
+        transfer.sign(&ALICE_KEY, 1337).unwrap();
+
+        let initcode = hex::decode("602a600052600760206000a100").unwrap();
+        let mut deploy = Transaction::new_contract_deployment(alice, initcode, U256::zero(), 1);
+        deploy.sign(&ALICE_KEY, 1337).unwrap();
+
+        let mut block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![transfer.clone(), deploy.clone()]);
+        block.miner = miner;
+        blockchain.add_block(block).unwrap();
+
+        let transfer_receipt = blockchain.get_transaction_receipt(&transfer.hash.unwrap()).unwrap();
+        let deploy_receipt = blockchain.get_transaction_receipt(&deploy.hash.unwrap()).unwrap();
+        assert_eq!(transfer_receipt.fee, U256::from(21000) * transfer.gas_price);
+        assert_eq!(deploy_receipt.fee, U256::from(deploy_receipt.gas_used) * deploy.gas_price);
+
+        let total_fees = transfer_receipt.fee + deploy_receipt.fee;
+        let base_fee = blockchain.get_latest_block().base_fee_per_gas;
+        let burned = U256::from(transfer_receipt.gas_used + deploy_receipt.gas_used) * base_fee;
+        assert_eq!(blockchain.state.get_balance(&alice), U256::from(ONE_ETH) - 100 - total_fees);
+        assert_eq!(blockchain.state.get_balance(&miner), total_fees - burned);
+        assert_eq!(blockchain.get_total_burned(), burned);
+        assert_eq!(blockchain.get_total_supply(), U256::from(ONE_ETH) - burned);
+    }
+
+    #[test]
+    fn test_transactions_outside_blocks_burn_their_base_fee() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        // Returns without doing anything.
+        let initcode = hex::decode("600080600a6000396000f3").unwrap();
+        let (contract, _) = blockchain.deploy_contract_with_revm(&ALICE_KEY, initcode, vec![], U256::zero(), 100_000).unwrap();
+        blockchain.call_contract_with_revm(&ALICE_KEY, contract, vec![], U256::from(100), 50_000).unwrap();
+
+        assert!(!blockchain.get_total_burned().is_zero());
+        assert_eq!(blockchain.get_total_supply() + blockchain.get_total_burned(), U256::from(ONE_ETH));
+    }
+
+    #[test]
+    fn test_base_fee_adjusts_and_dynamic_fee_transactions_pay_tips() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let bob = Address::from([2u8; 20]);
+        let miner = Address::from([99u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        let genesis_base_fee = blockchain.get_latest_block().base_fee_per_gas;
+        let block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![]);
+        blockchain.add_block(block).unwrap();
+        // Each empty parent lowers the base fee by 1/8.
+        assert_eq!(blockchain.get_latest_block().base_fee_per_gas, genesis_base_fee * 7 / 8);
+        let base_fee = blockchain.get_latest_block().next_base_fee();
+        assert_eq!(base_fee, genesis_base_fee * 49 / 64);
+
+        let tip = U256::from(2_000_000_000u64);
+        let mut tx = Transaction::new_transfer(alice, bob, U256::from(100), 0);
+        tx.set_dynamic_fee(base_fee * 2 + tip, tip);
+        tx.sign(&ALICE_KEY, 1337).unwrap();
+
+        let mut block = Block::new(2, blockchain.get_latest_block().hash.unwrap(), vec![tx.clone()]);
+        block.miner = miner;
+        blockchain.add_block(block).unwrap();
+
+        let receipt = blockchain.get_transaction_receipt(&tx.hash.unwrap()).unwrap();
+        assert_eq!(receipt.effective_gas_price, base_fee + tip);
+        assert_eq!(blockchain.state.get_balance(&miner), tip * 21000);
+        assert_eq!(blockchain.get_total_burned(), base_fee * 21000);
+        assert_eq!(blockchain.suggest_priority_fee(), tip);
+
+        let history = blockchain.fee_history(2, 2, &[50.0]);
+        assert_eq!(history.oldest_block, 1);
+        assert_eq!(history.base_fee_per_gas.len(), 3);
+        assert_eq!(history.base_fee_per_gas[1], base_fee);
+        assert_eq!(history.reward, vec![vec![U256::zero()], vec![tip]]);
+
+        // Fee caps below the base fee are rejected.
+        let mut underpriced = Transaction::new_transfer(alice, bob, U256::from(100), 1);
+        underpriced.set_dynamic_fee(U256::from(1), U256::from(1));
+        underpriced.sign(&ALICE_KEY, 1337).unwrap();
+        let block = Block::new(3, blockchain.get_latest_block().hash.unwrap(), vec![underpriced]);
+        assert!(blockchain.add_block(block).unwrap_err().contains("below the base fee"));
+
+        // A mined block cannot leave its base fee for the node to fill in.
+        let mut block = Block::new(3, blockchain.get_latest_block().hash.unwrap(), vec![]);
+        block.mine(1);
+        let expected = blockchain.get_latest_block().next_base_fee();
+        assert_eq!(blockchain.add_block(block), Err(format!("Invalid base fee. Expected {}, got 0", expected)));
+    }
+
+    #[test]
+    fn test_transfers_to_contracts_run_their_code() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        // Runtime stores CALLVALUE in slot 0, like a `receive()` that records deposits.
+        let initcode = hex::decode("6005600c60003960056000f33460005500").unwrap();
+        let contract = ContractUtils::calculate_create_address(&alice, 0);
+
+        let mut deploy = Transaction::new_contract_deployment(alice, initcode, U256::zero(), 0);
+        deploy.sign(&ALICE_KEY, 1337).unwrap();
+
+        // Claims to be a plain transfer, but the target has code by the time it executes.
+        let mut deposit = Transaction::new_transfer(alice, contract, U256::from(5), 1);
+        deposit.gas_limit = 50_000;
+        deposit.sign(&ALICE_KEY, 1337).unwrap();
+
+        let block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![deploy, deposit.clone()]);
+        blockchain.add_block(block).unwrap();
+
+        assert_eq!(blockchain.state.get_storage(&contract, &U256::zero()), U256::from(5));
+        assert_eq!(blockchain.state.get_balance(&contract), U256::from(5));
+        assert!(blockchain.get_latest_block().transactions[1].is_contract_call());
+        assert!(blockchain.get_transaction_receipt(&deposit.hash.unwrap()).unwrap().gas_used > 21000);
+    }
+
+    #[test]
+    fn test_failed_transactions_are_included_with_status_zero() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let bob = Address::from([2u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        // INVALID opcode: halts and burns the whole gas limit.
+        let mut halting = Transaction::new_contract_deployment(alice, vec![0xfe], U256::zero(), 0);
+        halting.gas_limit = 100_000;
+        halting.sign(&ALICE_KEY, 1337).unwrap();
+        // REVERT(0, 0): fails but returns unused gas.
+        let mut reverting = Transaction::new_contract_deployment(alice, hex::decode("60006000fd").unwrap(), U256::zero(), 1);
+        reverting.gas_limit = 100_000;
+        reverting.sign(&ALICE_KEY, 1337).unwrap();
+        let mut transfer = Transaction::new_transfer(alice, bob, U256::from(100), 2);
+        transfer.sign(&ALICE_KEY, 1337).unwrap();
+
+        let block = Block::new(
+            1,
+            blockchain.get_latest_block().hash.unwrap(),
+            vec![halting.clone(), reverting.clone(), transfer.clone()],
+        );
+        blockchain.add_block(block).unwrap();
+
+        let halted = blockchain.get_transaction_receipt(&halting.hash.unwrap()).unwrap();
+        assert!(!halted.status);
+        assert_eq!(halted.gas_used, 100_000);
+        assert_eq!(halted.contract_address, None);
+
+        let reverted = blockchain.get_transaction_receipt(&reverting.hash.unwrap()).unwrap();
+        assert!(!reverted.status);
+        assert!(reverted.gas_used < 100_000);
+
+        assert!(blockchain.get_transaction_receipt(&transfer.hash.unwrap()).unwrap().status);
+        assert_eq!(blockchain.state.get_nonce(&alice), 3);
+        assert_eq!(blockchain.state.get_balance(&bob), U256::from(100));
+        let fees = halted.fee + reverted.fee + U256::from(21000) * transfer.gas_price;
+        assert_eq!(blockchain.state.get_balance(&alice), U256::from(ONE_ETH) - 100 - fees);
+    }
+
+    #[test]
+    fn test_contracts_see_the_block_being_produced() {
+        let mut blockchain = Blockchain::new_with_chain_id(31337);
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let miner = Address::from([99u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        // Stores TIMESTAMP, COINBASE, NUMBER, GASLIMIT, BASEFEE, CHAINID and PREVRANDAO
+        // in slots 0 to 6.
+        let initcode = hex::decode("42600055416001554360025545600355486004554660055544600655").unwrap();
+        let mut deploy = Transaction::new_contract_deployment(alice, initcode, U256::zero(), 0);
+        deploy.sign(&ALICE_KEY, 31337).unwrap();
+
+        let mut block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![deploy]);
+        block.miner = miner;
+        block.timestamp = blockchain.get_latest_block().timestamp + 12;
+        blockchain.add_block(block).unwrap();
+
+        let block = blockchain.get_latest_block();
+        let contract = ContractUtils::calculate_create_address(&alice, 0);
+        let slot = |index: u64| blockchain.state.get_storage(&contract, &U256::from(index));
+        assert_eq!(slot(0), U256::from(block.timestamp));
+        assert_eq!(slot(1), U256::from_big_endian(miner.as_bytes()));
+        assert_eq!(slot(2), U256::from(1));
+        assert_eq!(slot(3), U256::from(block.gas_limit));
+        assert_eq!(slot(4), block.base_fee_per_gas);
+        assert_eq!(slot(5), U256::from(31337));
+        assert_eq!(slot(6), U256::from_big_endian(block.prev_randao().as_bytes()));
+    }
+
+    #[test]
+    fn test_blockhash_resolves_earlier_blocks() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        let block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![]);
+        blockchain.add_block(block).unwrap();
+
+        // Stores BLOCKHASH(NUMBER - 1) in slot 0, BLOCKHASH(0) in slot 1.
+        let initcode = hex::decode("6001430340600055600040600155").unwrap();
+        let mut deploy = Transaction::new_contract_deployment(alice, initcode, U256::zero(), 0);
+        deploy.sign(&ALICE_KEY, 1337).unwrap();
+        let block = Block::new(2, blockchain.get_latest_block().hash.unwrap(), vec![deploy]);
+        blockchain.add_block(block).unwrap();
+
+        let contract = ContractUtils::calculate_create_address(&alice, 0);
+        let slot = |index: u64| blockchain.state.get_storage(&contract, &U256::from(index));
+        let hash = |number: u64| U256::from_big_endian(blockchain.get_block_by_number(number).unwrap().hash.unwrap().as_bytes());
+        assert_eq!(slot(0), hash(1));
+        assert_eq!(slot(1), hash(0));
+    }
+
+    #[test]
+    fn test_block_hash_history_contract() {
+        let mut blockchain = Blockchain::new();
+        blockchain.enable_block_hash_history().unwrap();
+
+        for number in 1..=3 {
+            let block = Block::new(number, blockchain.get_latest_block().hash.unwrap(), vec![]);
+            blockchain.add_block(block).unwrap();
+        }
+
+        let hash = |number: u64| blockchain.get_block_by_number(number).unwrap().hash.unwrap();
+        let stored = blockchain.state.get_storage(&HISTORY_STORAGE_ADDRESS, &U256::from(1));
+        assert_eq!(stored, U256::from_big_endian(hash(1).as_bytes()));
+
+        let query = |number: u64| CallRequest {
+            to: Some(HISTORY_STORAGE_ADDRESS),
+            data: ContractUtils::encode_uint256(U256::from(number)),
+            ..Default::default()
+        };
+        let result = blockchain.call(&query(1), 3).unwrap();
+        assert!(result.success);
+        assert_eq!(result.return_data, hash(1).as_bytes());
+
+        // The current block and anything after it are not served.
+        assert!(!blockchain.call(&query(3), 3).unwrap().success);
+    }
+
+    #[test]
+    fn test_blocks_execute_under_their_scheduled_fork() {
+        let schedule = ForkSchedule::new_with_forks(vec![
+            (Hardfork::Paris, ForkCondition::Block(0)),
+            (Hardfork::Shanghai, ForkCondition::Block(2)),
+            (Hardfork::Cancun, ForkCondition::Block(2)),
+            (Hardfork::Prague, ForkCondition::Block(3)),
+        ]).unwrap();
+        let mut blockchain = Blockchain::new_with_fork_schedule(1337, schedule);
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        // PUSH1 1 PUSH0 SSTORE: PUSH0 only exists from Shanghai on.
+        let initcode = hex::decode("60015f55").unwrap();
+        for (nonce, number) in [(0, 1), (1, 2)] {
+            let mut deploy = Transaction::new_contract_deployment(alice, initcode.clone(), U256::zero(), nonce);
+            deploy.sign(&ALICE_KEY, 1337).unwrap();
+            let block = Block::new(number, blockchain.get_latest_block().hash.unwrap(), vec![deploy]);
+            blockchain.add_block(block).unwrap();
+        }
+
+        assert_eq!(blockchain.hardfork_at(blockchain.get_block_by_number(1).unwrap()), Hardfork::Paris);
+        assert_eq!(blockchain.spec_at(blockchain.get_latest_block()), SpecId::CANCUN);
+
+        let receipt = |number: u64| {
+            let hash = blockchain.get_block_by_number(number).unwrap().transactions[0].hash.unwrap();
+            blockchain.get_transaction_receipt(&hash).unwrap()
+        };
+        assert!(!receipt(1).status);
+        assert!(receipt(2).status);
+        let contract = ContractUtils::calculate_create_address(&alice, 1);
+        assert_eq!(blockchain.state.get_storage(&contract, &U256::zero()), U256::from(1));
+
+        // Prague brings the EIP-2935 history contract along.
+        assert!(blockchain.state.get_contract_code(&HISTORY_STORAGE_ADDRESS).is_empty());
+        let block = Block::new(3, blockchain.get_latest_block().hash.unwrap(), vec![]);
+        blockchain.add_block(block).unwrap();
+        let stored = blockchain.state.get_storage(&HISTORY_STORAGE_ADDRESS, &U256::from(2));
+        assert_eq!(stored, U256::from_big_endian(blockchain.get_block_by_number(2).unwrap().hash.unwrap().as_bytes()));
+    }
+
+    #[test]
+    fn test_call_reads_historical_state() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        // Stores 42 in slot 0. The runtime returns slot 0, or reverts with Error("nope")
+        // when given calldata.
+        let runtime = "36600f5760005460005260206000f3\
+                       5b6308c379a060e01b60005260206004526004602452636e6f706560e01b60445260646000fd";
+        let initcode = hex::decode(format!("602a6000556035601160003960356000f3{}", runtime)).unwrap();
+        let contract = ContractUtils::calculate_create_address(&alice, 0);
+
+        let mut deploy = Transaction::new_contract_deployment(alice, initcode, U256::zero(), 0);
+        deploy.sign(&ALICE_KEY, 1337).unwrap();
+        let block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![deploy]);
+        blockchain.add_block(block).unwrap();
+
+        let read = CallRequest { to: Some(contract), ..Default::default() };
+        let result = blockchain.call(&read, 1).unwrap();
+        assert!(result.success);
+        assert_eq!(ContractUtils::decode_uint256(&result.return_data), U256::from(42));
+
+        let revert = CallRequest { to: Some(contract), data: vec![1], ..Default::default() };
+        let result = blockchain.call(&revert, 1).unwrap();
+        assert!(!result.success);
+        assert_eq!(result.error, Some("execution reverted: nope".to_string()));
+
+        let mut transfer = Transaction::new_transfer(alice, contract, U256::from(100), 1);
+        transfer.gas_limit = 50_000; // The contract's code runs on plain transfers too.
+        transfer.sign(&ALICE_KEY, 1337).unwrap();
+        let block = Block::new(2, blockchain.get_latest_block().hash.unwrap(), vec![transfer]);
+        blockchain.add_block(block).unwrap();
+
+        assert_eq!(blockchain.state_at(1).unwrap().get_balance(&contract), U256::zero());
+        assert_eq!(blockchain.state_at(2).unwrap().get_balance(&contract), U256::from(100));
+        assert!(blockchain.call(&read, 1).unwrap().success);
+        // Before the deployment there is no code to run.
+        assert!(blockchain.call(&read, 0).unwrap().return_data.is_empty());
+        assert!(blockchain.call(&read, 5).is_err());
+    }
+
+    #[test]
+    fn test_state_history_rebuilds_older_states() {
+        let mut blockchain = Blockchain::new();
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let bob = Address::from([2u8; 20]);
+        let carol = Address::from([3u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        for (i, amount) in [10u64, 20].into_iter().enumerate() {
+            let mut tx = Transaction::new_transfer(alice, bob, U256::from(amount), i as u64);
+            tx.sign(&ALICE_KEY, 1337).unwrap();
+            let block = Block::new(i as u64 + 1, blockchain.get_latest_block().hash.unwrap(), vec![tx]);
+            blockchain.add_block(block).unwrap();
+            // Applied outside any block, so from this block's state on.
+            blockchain.state.set_balance(&carol, U256::from(i + 1));
+        }
+
+        // Entries only hold what each block touched: sender, recipient and fee recipient.
+        let (number, diff) = blockchain.state_history.back().unwrap();
+        assert_eq!(*number, 2);
+        assert_eq!(diff.accounts.len(), 3);
+
+        let genesis = blockchain.state_at(0).unwrap();
+        assert_eq!(genesis.get_balance(&alice), U256::from(ONE_ETH));
+        assert_eq!(genesis.get_balance(&bob), U256::zero());
+        assert!(!genesis.account_exists(&carol));
+
+        let first = blockchain.state_at(1).unwrap();
+        assert_eq!(first.get_balance(&bob), U256::from(10));
+        assert_eq!(first.get_nonce(&alice), 1);
+        assert_eq!(first.get_balance(&carol), U256::from(1));
+
+        let head = blockchain.state_at(2).unwrap();
+        assert_eq!(head.get_balance(&bob), U256::from(30));
+        assert_eq!(head.get_balance(&carol), U256::from(2));
+        assert!(blockchain.state_at(3).is_err());
+    }
+
+    #[test]
+    fn test_call_with_state_and_block_overrides() {
+        let blockchain = Blockchain::new();
+        let contract = Address::repeat_byte(0x42);
+        let bob = Address::repeat_byte(0xb0);
+
+        // Returns SLOAD(0) + NUMBER.
+        let code = hex::decode("600054430160005260206000f3").unwrap();
+        let mut overrides = CallOverrides::default();
+        overrides.accounts.insert(contract, AccountOverride {
+            code: Some(code),
+            state_diff: HashMap::from([(U256::zero(), U256::from(5))]),
+            ..Default::default()
+        });
+        overrides.accounts.insert(bob, AccountOverride { balance: Some(U256::from(ONE_ETH)), ..Default::default() });
+        overrides.block.number = Some(100);
+
+        let read = CallRequest { from: Some(bob), to: Some(contract), value: U256::from(ONE_ETH), ..Default::default() };
+        let result = blockchain.call_with_overrides(&read, 0, &overrides).unwrap();
+        assert!(result.success);
+        assert_eq!(ContractUtils::decode_uint256(&result.return_data), U256::from(105));
+
+        let (gas, result) = blockchain.estimate_gas_with_overrides(&read, 0, &overrides).unwrap();
+        assert!(result.success);
+        assert!(gas > 21_000);
+
+        // Nothing leaks into the chain: without overrides bob cannot pay and there is no code.
+        assert!(blockchain.call(&read, 0).is_err());
+        assert!(blockchain.state.get_account(&contract).is_none());
+    }
+
+    #[test]
+    fn test_simulate_runs_dependent_calls_across_blocks() {
+        let blockchain = Blockchain::new();
+        let contract = Address::repeat_byte(0x42);
+        let bob = Address::repeat_byte(0xb0);
+
+        // Stores its first calldata word in slot 0, or returns slot 0 when called without data.
+        let code = hex::decode("36600f5760005460005260206000f35b60003560005500").unwrap();
+        let mut overrides = CallOverrides::default();
+        overrides.accounts.insert(contract, AccountOverride { code: Some(code), ..Default::default() });
+
+        let store = CallRequest {
+            from: Some(bob),
+            to: Some(contract),
+            data: ContractUtils::encode_uint256(U256::from(7)),
+            ..Default::default()
+        };
+        let read = CallRequest { from: Some(bob), to: Some(contract), ..Default::default() };
+        let starved = CallRequest { gas: Some(21_100), ..read.clone() };
+        let blocks = vec![
+            SimulatedBlockCalls { overrides, calls: vec![store, read.clone()] },
+            SimulatedBlockCalls { overrides: CallOverrides::default(), calls: vec![read, starved] },
+        ];
+
+        let simulated = blockchain.simulate(0, &blocks, false).unwrap();
+        assert_eq!(simulated.len(), 2);
+        assert_eq!(simulated[0].block.number, 1);
+        assert_eq!(simulated[1].block.parent_hash, simulated[0].block.hash.unwrap());
+        assert_eq!(simulated[1].block.transactions[0].nonce, 2);
+
+        let first = &simulated[0].results;
+        assert!(first[0].success);
+        assert_eq!(ContractUtils::decode_uint256(&first[1].return_data), U256::from(7));
+        assert_eq!(simulated[0].block.gas_used, first[0].gas_used + first[1].gas_used);
+
+        let second = &simulated[1].results;
+        assert_eq!(ContractUtils::decode_uint256(&second[0].return_data), U256::from(7));
+        assert!(!second[1].success);
+
+        // The chain itself is untouched, and with validation bob has to pay for gas.
+        assert!(blockchain.state.get_account(&contract).is_none());
+        assert!(blockchain.simulate(0, &blocks, true).is_err());
+    }
+
+    #[test]
+    fn test_simulate_resolves_block_hashes_across_number_gaps() {
+        let blockchain = Blockchain::new();
+        let contract = Address::repeat_byte(0x42);
+
+        // Returns BLOCKHASH of its first calldata word.
+        let code = hex::decode("6000354060005260206000f3").unwrap();
+        let mut overrides = CallOverrides::default();
+        overrides.accounts.insert(contract, AccountOverride { code: Some(code), ..Default::default() });
+
+        let hash_of = |number: u64| CallRequest {
+            to: Some(contract),
+            data: ContractUtils::encode_uint256(U256::from(number)),
+            // Without validation the nonce is not checked.
+            nonce: Some(7),
+            ..Default::default()
+        };
+        let mut skip_ahead = CallOverrides::default();
+        skip_ahead.block.number = Some(5);
+        let blocks = vec![
+            SimulatedBlockCalls { overrides, calls: vec![] },
+            SimulatedBlockCalls { overrides: skip_ahead, calls: vec![hash_of(0), hash_of(1), hash_of(3)] },
+        ];
+
+        let simulated = blockchain.simulate(0, &blocks, false).unwrap();
+        let results = &simulated[1].results;
+        let hash = |index: usize| H256::from_slice(&results[index].return_data);
+        assert_eq!(hash(0), blockchain.blocks[0].hash.unwrap());
+        assert_eq!(hash(1), simulated[0].block.hash.unwrap());
+        assert_eq!(hash(2), H256::zero());
+    }
+
+    #[test]
+    fn test_simulate_records_parent_hashes_in_history_contract() {
+        let mut blockchain = Blockchain::new();
+        blockchain.enable_block_hash_history().unwrap();
+
+        let read = |number: u64| CallRequest {
+            to: Some(HISTORY_STORAGE_ADDRESS),
+            data: ContractUtils::encode_uint256(U256::from(number)),
+            ..Default::default()
+        };
+        let blocks = vec![
+            SimulatedBlockCalls { overrides: CallOverrides::default(), calls: vec![] },
+            SimulatedBlockCalls { overrides: CallOverrides::default(), calls: vec![read(0), read(1)] },
+        ];
+
+        let simulated = blockchain.simulate(0, &blocks, false).unwrap();
+        let results = &simulated[1].results;
+        assert_eq!(H256::from_slice(&results[0].return_data), blockchain.blocks[0].hash.unwrap());
+        assert_eq!(H256::from_slice(&results[1].return_data), simulated[0].block.hash.unwrap());
+        assert_eq!(blockchain.state.get_storage(&HISTORY_STORAGE_ADDRESS, &U256::zero()), U256::zero());
+    }
+
+    #[test]
+    fn test_asset_changes_for_simulated_and_mined_transactions() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        // Keeps any ETH sent to it and emits an ERC-20 Transfer of 5 of itself to the caller.
+        let contract = Address::repeat_byte(0x42);
+        let code = "600560005233307fddf252ad1be2c89b69c2b068fc378daa952ba7f163c4a11628f55a4df523b3ef60206000a300";
+        blockchain.state.get_account_mut(&contract).set_code(hex::decode(code).unwrap());
+
+        let request = CallRequest { from: Some(alice), to: Some(contract), value: U256::from(100), ..Default::default() };
+        let (result, changes) = blockchain.simulate_asset_changes(&request, 0).unwrap();
+        assert!(result.success);
+        assert_eq!(changes.native[&alice], Delta { gained: U256::zero(), lost: U256::from(100) });
+        assert_eq!(changes.native[&contract], Delta { gained: U256::from(100), lost: U256::zero() });
+        assert_eq!(changes.erc20[&(contract, alice)], Delta { gained: U256::from(5), lost: U256::zero() });
+        assert_eq!(changes.erc20[&(contract, contract)], Delta { gained: U256::zero(), lost: U256::from(5) });
+
+        let mut deposit = Transaction::new_transfer(alice, contract, U256::from(100), 0);
+        deposit.gas_limit = 50_000;
+        deposit.sign(&ALICE_KEY, 1337).unwrap();
+        let block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![deposit.clone()]);
+        blockchain.add_block(block).unwrap();
+
+        // Mined, alice also pays for gas and the miner collects the tip.
+        let hash = deposit.hash.unwrap();
+        let (receipt, changes) = blockchain.transaction_token_changes(&hash).unwrap();
+        let fee = U256::from(receipt.gas_used) * receipt.effective_gas_price;
+        assert_eq!(changes.erc20[&(contract, alice)].gained, U256::from(5));
+        assert_eq!(blockchain.transaction_native_changes(&hash).unwrap()[&alice].lost, U256::from(100) + fee);
+
+        // Once the parent state is pruned only the native deltas become unavailable.
+        blockchain.state_history.clear();
+        assert!(blockchain.transaction_token_changes(&hash).is_ok());
+        assert!(blockchain.transaction_native_changes(&hash).unwrap_err().starts_with("state pruned"));
+    }
+
+    #[test]
+    fn test_trace_transaction_replays_earlier_transactions_in_block() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        // Runtime: increments slot 0.
+        let initcode = hex::decode("600a600c600039600a6000f360005460010160005500").unwrap();
+        let contract = ContractUtils::calculate_create_address(&alice, 0);
+        let mut deploy = Transaction::new_contract_deployment(alice, initcode, U256::zero(), 0);
+        deploy.sign(&ALICE_KEY, 1337).unwrap();
+        let block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![deploy]);
+        blockchain.add_block(block).unwrap();
+
+        let increments: Vec<Transaction> = (1..=2).map(|nonce| {
+            let mut tx = Transaction::new_contract_call(alice, contract, vec![], U256::zero(), nonce);
+            tx.gas_limit = 100_000;
+            tx.sign(&ALICE_KEY, 1337).unwrap();
+            tx
+        }).collect();
+        let hash = increments[1].hash.unwrap();
+        let block = Block::new(2, blockchain.get_latest_block().hash.unwrap(), increments);
+        blockchain.add_block(block).unwrap();
+
+        let trace = blockchain.trace_transaction(&hash, StructLogger::new(StructLoggerConfig::default())).unwrap();
+        assert!(trace.result.success);
+        assert_eq!(trace.result.gas_used, blockchain.get_transaction_receipt(&hash).unwrap().gas_used);
+
+        let logs = trace.inspector.into_logs();
+        let slot = H256::zero();
+        let sload = logs.iter().find(|log| log.op == "SLOAD").unwrap();
+        assert_eq!(sload.storage.as_ref().unwrap()[&slot], H256::from_low_u64_be(1));
+        let sstore = logs.iter().find(|log| log.op == "SSTORE").unwrap();
+        assert_eq!(sstore.storage.as_ref().unwrap()[&slot], H256::from_low_u64_be(2));
+
+        // Tracing must not have touched the live state.
+        assert_eq!(blockchain.state.get_storage(&contract, &U256::zero()), U256::from(2));
+
+        let request = CallRequest { to: Some(contract), ..Default::default() };
+        let trace = blockchain.trace_call(&request, 2, StructLogger::new(StructLoggerConfig::default())).unwrap();
+        let sload = trace.inspector.into_logs().into_iter().find(|log| log.op == "SLOAD").unwrap();
+        assert_eq!(sload.storage.unwrap()[&slot], H256::from_low_u64_be(2));
+    }
+
+    #[test]
+    fn test_estimate_gas_finds_minimal_limit() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        let transfer = CallRequest { from: Some(alice), to: Some(Address::from([2u8; 20])), value: U256::from(1), ..Default::default() };
+        let (gas, _) = blockchain.estimate_gas(&transfer, 0).unwrap();
+        assert_eq!(gas, 21000);
+
+        // Writes a fresh storage slot, then reverts with empty data when given calldata.
+        let runtime = hex::decode("36600a576001600055005b60006000fd").unwrap();
+        let initcode = [hex::decode("6010600c60003960106000f3").unwrap(), runtime].concat();
+        let deploy = CallRequest { from: Some(alice), data: initcode.clone(), ..Default::default() };
+        let (deploy_gas, result) = blockchain.estimate_gas(&deploy, 0).unwrap();
+        assert!(result.success);
+
+        let mut deploy_tx = Transaction::new_contract_deployment(alice, initcode, U256::zero(), 0);
+        deploy_tx.gas_limit = deploy_gas;
+        deploy_tx.sign(&ALICE_KEY, 1337).unwrap();
+        let block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![deploy_tx]);
+        blockchain.add_block(block).unwrap();
+        let contract = ContractUtils::calculate_create_address(&alice, 0);
+
+        let store = CallRequest { from: Some(alice), to: Some(contract), ..Default::default() };
+        let (store_gas, _) = blockchain.estimate_gas(&store, 1).unwrap();
+        let too_little = CallRequest { gas: Some(store_gas - 1), ..store.clone() };
+        let just_enough = CallRequest { gas: Some(store_gas), ..store.clone() };
+        assert!(blockchain.call(&too_little, 1).unwrap().is_out_of_gas());
+        assert!(blockchain.call(&just_enough, 1).unwrap().success);
+
+        let reverting = CallRequest { data: vec![1], ..store };
+        let (_, result) = blockchain.estimate_gas(&reverting, 1).unwrap();
+        assert!(result.is_revert());
+        assert!(!result.is_out_of_gas());
+    }
+
     #[test]
     fn test_chain_validation() {
         let mut blockchain = Blockchain::new();
+        blockchain.state.set_balance(&address_from_secret_key(&ALICE_KEY).unwrap(), U256::from(ONE_ETH));
 
         for i in 1..=3 {
-            let alice = Address::from([1u8; 20]);
+            let alice = address_from_secret_key(&ALICE_KEY).unwrap();
             let bob = Address::from([2u8; 20]);
 
             let mut tx = Transaction::new_transfer(alice, bob, U256::from(10), i - 1);
-            tx.set_hash();
+            tx.sign(&ALICE_KEY, 1337).unwrap();
 
-            let block = Block::new(
+            let mut block = Block::new(
                 i,
                 blockchain.get_latest_block().hash.unwrap(),
                 vec![tx],
             );
+            blockchain.prepare_block(&mut block).unwrap();
+            block.mine(3);
 
             blockchain.add_block(block).unwrap();
         }
@@ -436,16 +1952,50 @@ mod tests {
         assert!(validation.is_ok());
     }
 
+    #[test]
+    fn test_mined_blocks_must_commit_to_their_execution() {
+        let mut blockchain = Blockchain::new();
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let bob = Address::from([2u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        let mut tx = Transaction::new_transfer(alice, bob, U256::from(10), 0);
+        tx.sign(&ALICE_KEY, 1337).unwrap();
+        let mut block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![tx]);
+        blockchain.prepare_block(&mut block).unwrap();
+        assert_eq!(block.gas_used, 21_000);
+        assert_ne!(block.receipts_root, crate::trie::EMPTY_TRIE_ROOT);
+        assert_eq!(blockchain.state.get_nonce(&alice), 0);
+
+        // The hash matches the header, but the header does not match the execution.
+        let mut forged = block.clone();
+        forged.state_root = H256::repeat_byte(1);
+        forged.mine(1);
+        assert!(blockchain.add_block(forged).unwrap_err().starts_with("Invalid state root"));
+
+        let mut tampered = block.clone();
+        tampered.mine(1);
+        tampered.gas_used += 1;
+        assert_eq!(blockchain.add_block(tampered), Err("Block hash does not match its header".to_string()));
+        assert_eq!(blockchain.state.get_nonce(&alice), 0);
+
+        block.mine(1);
+        blockchain.add_block(block.clone()).unwrap();
+        assert_eq!(blockchain.get_latest_block().hash, block.hash);
+        assert_eq!(blockchain.state.get_balance(&bob), U256::from(10));
+    }
+
     #[test]
     fn test_transaction_history() {
         let mut blockchain = Blockchain::new();
 
-        let alice = Address::from([1u8; 20]);
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
         let bob = Address::from([2u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
 
         for i in 0..3 {
             let mut tx = Transaction::new_transfer(alice, bob, U256::from(100), i);
-            tx.set_hash();
+            tx.sign(&ALICE_KEY, 1337).unwrap();
 
             let block = Block::new(
                 i + 1,
@@ -463,18 +2013,84 @@ mod tests {
         assert_eq!(bob_txs.len(), 3);
     }
 
+    #[test]
+    fn test_transaction_history_includes_internal_transfers() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let bob = Address::from([2u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        // Forwards whatever it receives to bob.
+        let forwarder = Address::repeat_byte(0x42);
+        let code = "6000600060006000347302020202020202020202020202020202020202025af100";
+        blockchain.state.get_account_mut(&forwarder).set_code(hex::decode(code).unwrap());
+
+        let mut tx = Transaction::new_transfer(alice, forwarder, U256::from(100), 0);
+        tx.gas_limit = 100_000;
+        tx.sign(&ALICE_KEY, 1337).unwrap();
+        let block = Block::new(1, blockchain.get_latest_block().hash.unwrap(), vec![tx.clone()]);
+        blockchain.add_block(block).unwrap();
+
+        assert_eq!(blockchain.state.get_balance(&bob), U256::from(100));
+        let operations = blockchain.get_internal_operations(&tx.hash.unwrap()).unwrap();
+        assert_eq!(operations, [InternalOperation {
+            kind: InternalOperationKind::Transfer,
+            from: forwarder,
+            to: bob,
+            value: U256::from(100),
+        }]);
+
+        let bob_txs = blockchain.get_transactions_for_address(&bob);
+        assert_eq!(bob_txs.len(), 1);
+        assert_eq!(bob_txs[0].hash, tx.hash);
+        // The forwarder is both recipient and sender of the internal transfer, but listed once.
+        assert_eq!(blockchain.get_transactions_for_address(&forwarder).len(), 1);
+    }
+
+    #[test]
+    fn test_transaction_lookup_by_hash() {
+        let mut blockchain = Blockchain::new();
+
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
+        let bob = Address::from([2u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
+
+        let mut hashes = Vec::new();
+        for i in 0..2 {
+            let mut first = Transaction::new_transfer(alice, bob, U256::from(100), 2 * i);
+            first.sign(&ALICE_KEY, 1337).unwrap();
+            let mut second = Transaction::new_transfer(alice, bob, U256::from(200), 2 * i + 1);
+            second.sign(&ALICE_KEY, 1337).unwrap();
+            hashes.push((first.hash.unwrap(), i + 1, 0));
+            hashes.push((second.hash.unwrap(), i + 1, 1));
+
+            let block = Block::new(i + 1, blockchain.get_latest_block().hash.unwrap(), vec![first, second]);
+            blockchain.add_block(block).unwrap();
+        }
+
+        for (hash, block_number, index) in hashes {
+            let (tx, block, position) = blockchain.get_transaction_by_hash(&hash).unwrap();
+            assert_eq!(tx.hash, Some(hash));
+            assert_eq!(block.number, block_number);
+            assert_eq!(position, index);
+        }
+        assert!(blockchain.get_transaction_by_hash(&H256::from([7u8; 32])).is_none());
+    }
+
     #[test]
     fn test_balance_tracking() {
         let mut blockchain = Blockchain::new();
 
-        let alice = Address::from([1u8; 20]);
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
         let bob = Address::from([2u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
 
         let initial_alice_balance = blockchain.state.get_balance(&alice);
         let initial_bob_balance = blockchain.state.get_balance(&bob);
 
         let mut tx = Transaction::new_transfer(alice, bob, U256::from(1000), 0);
-        tx.set_hash();
+        tx.sign(&ALICE_KEY, 1337).unwrap();
 
         let block = Block::new(
             1,
@@ -510,11 +2126,12 @@ mod tests {
     fn test_blockchain_stats() {
         let mut blockchain = Blockchain::new();
 
-        let alice = Address::from([1u8; 20]);
+        let alice = address_from_secret_key(&ALICE_KEY).unwrap();
         let bob = Address::from([2u8; 20]);
+        blockchain.state.set_balance(&alice, U256::from(ONE_ETH));
 
         let mut tx = Transaction::new_transfer(alice, bob, U256::from(100), 0);
-        tx.set_hash();
+        tx.sign(&ALICE_KEY, 1337).unwrap();
 
         let block = Block::new(
             1,
@@ -1,12 +1,24 @@
 use crate::account::WorldState;
+use crate::block::Block;
+use crate::revert::decode_revert_reason;
+use crate::transaction::{Transaction, TransactionType};
 use ethereum_types::{Address, U256, H256};
 use revm::{
+    db::{AccountState as DbAccountState, CacheDB},
+    interpreter::{
+        gas::validate_initial_tx_gas, CallInputs, CallOutcome, CallScheme, CreateInputs, CreateOutcome, InstructionResult, Interpreter,
+        InterpreterResult, OpCode,
+    },
     primitives::{
-        AccountInfo, Bytecode, ExecutionResult, Output, TransactTo,
-        B256, U256 as rU256, Address as rAddress, Bytes,
+        AccountInfo, Bytecode, CreateScheme, EvmState, ExecutionResult, HaltReason, Output, ResultAndState, SpecId,
+        TransactTo, B256, KECCAK_EMPTY, U256 as rU256, Address as rAddress, Bytes,
     },
-    Database, DatabaseCommit, Evm, EvmBuilder, InMemoryDB,
+    inspector_handle_register, Database, DatabaseCommit, DatabaseRef, Evm, EvmBuilder, EvmContext, Inspector,
 };
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::convert::Infallible;
+use std::fmt::Debug;
 
 fn ethereum_u256_to_revm_u256(value: U256) -> rU256 {
     let mut bytes = [0u8; 32];
@@ -23,57 +35,245 @@ fn h256_to_u256(value: H256) -> U256 {
     U256::from_big_endian(value.as_bytes())
 }
 
-pub struct RevmExecutor {
-    pub evm: Evm<'static, (), InMemoryDB>,
+fn revm_u256_to_h256(value: rU256) -> H256 {
+    H256::from(value.to_be_bytes::<32>())
+}
+
+fn revm_access_list(tx: &Transaction) -> Vec<(rAddress, Vec<rU256>)> {
+    tx.access_list.iter()
+        .map(|item| (
+            rAddress::from_slice(item.address.as_bytes()),
+            item.storage_keys.iter().map(|key| ethereum_u256_to_revm_u256(h256_to_u256(*key))).collect(),
+        ))
+        .collect()
+}
+
+/// Nonce of `address` and whether it has code, read through any state revm executes on.
+pub fn nonce_and_has_code<S: DatabaseRef<Error = Infallible>>(state: &S, address: &Address) -> (u64, bool) {
+    let Ok(info) = state.basic_ref(rAddress::from_slice(address.as_bytes()));
+    info.map_or((0, false), |info| (info.nonce, info.code_hash != KECCAK_EMPTY))
+}
+
+/// Gas `tx` costs before any code runs: the base cost plus its calldata, access list and,
+/// for creations, initcode.
+pub fn intrinsic_gas(tx: &Transaction, spec_id: SpecId) -> u64 {
+    validate_initial_tx_gas(spec_id, &tx.data, tx.to.is_none(), &revm_access_list(tx))
+}
+
+// revm reads accounts straight out of the world state and writes its post-execution
+// diff back through `commit`, so a transaction only ever touches the accounts it uses.
+impl DatabaseRef for WorldState {
+    type Error = Infallible;
+
+    fn basic_ref(&self, address: rAddress) -> Result<Option<AccountInfo>, Self::Error> {
+        let account = match self.get_account(&Address::from_slice(address.as_slice())) {
+            Some(account) => account,
+            None => return Ok(None),
+        };
+
+        let (code_hash, code) = if account.code.is_empty() {
+            (KECCAK_EMPTY, None)
+        } else {
+            (
+                B256::from_slice(account.code_hash.as_bytes()),
+                Some(Bytecode::new_raw(Bytes::from(account.code.clone()))),
+            )
+        };
+
+        Ok(Some(AccountInfo {
+            balance: ethereum_u256_to_revm_u256(account.balance),
+            nonce: account.nonce,
+            code_hash,
+            code,
+        }))
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        // Only reached when `basic_ref` did not hand out the code itself.
+        let code_hash = H256::from_slice(code_hash.as_slice());
+        Ok(self.accounts.values()
+            .find(|account| account.is_contract() && account.code_hash == code_hash)
+            .map(|account| Bytecode::new_raw(Bytes::from(account.code.clone())))
+            .unwrap_or_default())
+    }
+
+    fn storage_ref(&self, address: rAddress, index: rU256) -> Result<rU256, Self::Error> {
+        let value = self.get_storage(
+            &Address::from_slice(address.as_slice()),
+            &revm_u256_to_ethereum_u256(index),
+        );
+        Ok(ethereum_u256_to_revm_u256(value))
+    }
+
+    fn block_hash_ref(&self, _number: rU256) -> Result<B256, Self::Error> {
+        Ok(B256::ZERO)
+    }
+}
+
+impl Database for WorldState {
+    type Error = Infallible;
+
+    fn basic(&mut self, address: rAddress) -> Result<Option<AccountInfo>, Self::Error> {
+        self.basic_ref(address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.code_by_hash_ref(code_hash)
+    }
+
+    fn storage(&mut self, address: rAddress, index: rU256) -> Result<rU256, Self::Error> {
+        self.storage_ref(address, index)
+    }
+
+    fn block_hash(&mut self, number: rU256) -> Result<B256, Self::Error> {
+        self.block_hash_ref(number)
+    }
+}
+
+/// Commits leave `state_root` stale; `Blockchain::add_block` recomputes it once per block.
+impl DatabaseCommit for WorldState {
+    fn commit(&mut self, changes: EvmState) {
+        apply_state_changes(self, changes);
+    }
+}
+
+/// Execution database: a world state plus the chain it belongs to, so BLOCKHASH sees real
+/// block hashes. `blocks` holds every block before the one executing; revm itself limits
+/// lookups to the most recent 256.
+pub struct ChainDatabase<'a, S> {
+    pub state: S,
+    pub blocks: &'a [Block],
+    /// Hashes of simulated blocks built on top of `blocks`, by number.
+    pub simulated_hashes: Option<&'a HashMap<u64, H256>>,
+}
+
+impl<'a, S> ChainDatabase<'a, S> {
+    pub fn new(state: S, blocks: &'a [Block]) -> Self {
+        ChainDatabase { state, blocks, simulated_hashes: None }
+    }
+
+    /// Like `new`, also resolving the simulated blocks in `simulated_hashes`. Numbers in
+    /// neither, such as those skipped by a block number override, hash to zero.
+    pub fn new_with_simulated_hashes(state: S, blocks: &'a [Block], simulated_hashes: &'a HashMap<u64, H256>) -> Self {
+        ChainDatabase { state, blocks, simulated_hashes: Some(simulated_hashes) }
+    }
+}
+
+impl<S: DatabaseRef<Error = Infallible>> DatabaseRef for ChainDatabase<'_, S> {
+    type Error = Infallible;
+
+    fn basic_ref(&self, address: rAddress) -> Result<Option<AccountInfo>, Self::Error> {
+        self.state.basic_ref(address)
+    }
+
+    fn code_by_hash_ref(&self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.state.code_by_hash_ref(code_hash)
+    }
+
+    fn storage_ref(&self, address: rAddress, index: rU256) -> Result<rU256, Self::Error> {
+        self.state.storage_ref(address, index)
+    }
+
+    fn block_hash_ref(&self, number: rU256) -> Result<B256, Self::Error> {
+        let Ok(number) = u64::try_from(number) else {
+            return Ok(B256::ZERO);
+        };
+        let hash = usize::try_from(number).ok()
+            .and_then(|index| self.blocks.get(index))
+            .and_then(|block| block.hash)
+            .or_else(|| self.simulated_hashes?.get(&number).copied());
+        Ok(hash.map_or(B256::ZERO, |hash| B256::from_slice(hash.as_bytes())))
+    }
+}
+
+impl<S: DatabaseRef<Error = Infallible>> Database for ChainDatabase<'_, S> {
+    type Error = Infallible;
+
+    fn basic(&mut self, address: rAddress) -> Result<Option<AccountInfo>, Self::Error> {
+        self.basic_ref(address)
+    }
+
+    fn code_by_hash(&mut self, code_hash: B256) -> Result<Bytecode, Self::Error> {
+        self.code_by_hash_ref(code_hash)
+    }
+
+    fn storage(&mut self, address: rAddress, index: rU256) -> Result<rU256, Self::Error> {
+        self.storage_ref(address, index)
+    }
+
+    fn block_hash(&mut self, number: rU256) -> Result<B256, Self::Error> {
+        self.block_hash_ref(number)
+    }
+}
+
+impl<S: DatabaseCommit> DatabaseCommit for ChainDatabase<'_, S> {
+    fn commit(&mut self, changes: EvmState) {
+        self.state.commit(changes);
+    }
+}
+
+pub struct RevmExecutor<'a, DB: Database, EXT = ()> {
+    pub evm: Evm<'a, EXT, DB>,
 }
 
-impl RevmExecutor {
-    pub fn new(block_number: u64, block_timestamp: u64, coinbase: Address, gas_limit: u64) -> Self {
-        let mut evm = EvmBuilder::default()
-            .with_db(InMemoryDB::default())
+impl<DB: Database> RevmExecutor<'_, DB>
+where
+    DB::Error: Debug,
+{
+    /// Executor whose block environment is the header of `block`, the block being produced,
+    /// imported or (for calls) read from, running the rules of `spec_id`.
+    pub fn new(db: DB, block: &Block, chain_id: u64, spec_id: SpecId) -> Self {
+        let evm = EvmBuilder::default()
+            .with_db(db)
+            .with_spec_id(spec_id)
             .build();
 
-        evm.context.evm.env.cfg.chain_id = 1337;
+        RevmExecutor::new_with_evm(evm, block, chain_id)
+    }
+}
 
-        evm.context.evm.env.block.number = rU256::from(block_number);
-        evm.context.evm.env.block.timestamp = rU256::from(block_timestamp);
-        evm.context.evm.env.block.coinbase = rAddress::from_slice(coinbase.as_bytes());
-        evm.context.evm.env.block.gas_limit = rU256::from(gas_limit);
-        evm.context.evm.env.block.basefee = rU256::from(1_000_000_000u64); // 1 gwei
+impl<DB: Database, I: Inspector<DB>> RevmExecutor<'_, DB, I>
+where
+    DB::Error: Debug,
+{
+    /// Like `new`, with `inspector` observing every step of execution.
+    pub fn new_with_inspector(db: DB, block: &Block, chain_id: u64, spec_id: SpecId, inspector: I) -> Self {
+        let evm = EvmBuilder::default()
+            .with_db(db)
+            .with_external_context(inspector)
+            .with_spec_id(spec_id)
+            .append_handler_register(inspector_handle_register)
+            .build();
 
-        RevmExecutor { evm }
+        RevmExecutor::new_with_evm(evm, block, chain_id)
     }
 
-    pub fn load_state_from_world(&mut self, state: &WorldState) -> Result<(), String> {
-        for (address, account) in &state.accounts {
-            let account_info = AccountInfo {
-                balance: ethereum_u256_to_revm_u256(account.balance),
-                nonce: account.nonce,
-                code_hash: B256::from_slice(account.code_hash.as_bytes()),
-                code: if account.code.is_empty() {
-                    None
-                } else {
-                    Some(Bytecode::new_raw(Bytes::from(account.code.clone())))
-                },
-            };
-
-            self.evm.context.evm.db.insert_account_info(
-                rAddress::from_slice(address.as_bytes()),
-                account_info,
-            );
+    pub fn into_inspector(self) -> I {
+        self.evm.into_context().external
+    }
+}
 
-            for (key, value) in &account.storage {
-                self.evm.context.evm.db.insert_account_storage(
-                    rAddress::from_slice(address.as_bytes()),
-                    ethereum_u256_to_revm_u256(*key),
-                    ethereum_u256_to_revm_u256(*value),
-                ).map_err(|e| format!("Storage load error: {:?}", e))?;
-            }
-        }
-        Ok(())
+impl<'a, DB: Database, EXT> RevmExecutor<'a, DB, EXT>
+where
+    DB::Error: Debug,
+{
+    fn new_with_evm(mut evm: Evm<'a, EXT, DB>, block: &Block, chain_id: u64) -> Self {
+        evm.context.evm.env.cfg.chain_id = chain_id;
+
+        let block_env = &mut evm.context.evm.env.block;
+        block_env.number = rU256::from(block.number);
+        block_env.timestamp = rU256::from(block.timestamp);
+        block_env.coinbase = rAddress::from_slice(block.miner.as_bytes());
+        block_env.gas_limit = rU256::from(block.gas_limit);
+        block_env.basefee = ethereum_u256_to_revm_u256(block.base_fee_per_gas);
+        block_env.difficulty = rU256::from(block.difficulty);
+        block_env.prevrandao = Some(B256::from_slice(block.prev_randao().as_bytes()));
+
+        RevmExecutor { evm }
     }
 
-    pub fn execute_transaction(
+    #[allow(clippy::too_many_arguments)]
+    fn set_tx_env(
         &mut self,
         from: Address,
         to: Option<Address>,
@@ -81,24 +281,117 @@ impl RevmExecutor {
         data: Vec<u8>,
         gas_limit: u64,
         gas_price: U256,
-        nonce: u64,
-    ) -> Result<ContractExecutionResult, String> {
+        nonce: Option<u64>,
+    ) {
         self.evm.context.evm.env.tx.caller = rAddress::from_slice(from.as_bytes());
         self.evm.context.evm.env.tx.gas_limit = gas_limit;
         self.evm.context.evm.env.tx.gas_price = ethereum_u256_to_revm_u256(gas_price);
         self.evm.context.evm.env.tx.value = ethereum_u256_to_revm_u256(value);
         self.evm.context.evm.env.tx.data = Bytes::from(data);
-        self.evm.context.evm.env.tx.nonce = Some(nonce);
+        self.evm.context.evm.env.tx.nonce = nonce;
 
         self.evm.context.evm.env.tx.transact_to = match to {
             Some(addr) => TransactTo::Call(rAddress::from_slice(addr.as_bytes())),
             None => TransactTo::Create,
         };
+    }
 
-        let result = self.evm.transact_commit()
+    fn set_transaction_env(&mut self, tx: &Transaction) {
+        self.set_tx_env(tx.from, tx.to, tx.value, tx.data.clone(), tx.gas_limit, tx.gas_price, Some(tx.nonce));
+
+        let tx_env = &mut self.evm.context.evm.env.tx;
+        tx_env.chain_id = tx.chain_id;
+        tx_env.gas_priority_fee = tx.max_priority_fee_per_gas.map(ethereum_u256_to_revm_u256);
+        tx_env.access_list = revm_access_list(tx);
+    }
+
+    /// Runs a signed transaction exactly as block execution would, without committing it.
+    /// Returns the post-state of every account it touched alongside the result.
+    pub fn replay_transaction(&mut self, tx: &Transaction) -> Result<(ContractExecutionResult, EvmState), String> {
+        self.set_transaction_env(tx);
+        self.transact()
+    }
+
+    /// Executes without committing anything to the underlying database.
+    pub fn simulate_transaction(
+        &mut self,
+        from: Address,
+        to: Option<Address>,
+        value: U256,
+        data: Vec<u8>,
+        gas_limit: u64,
+        gas_price: U256,
+    ) -> Result<ContractExecutionResult, String> {
+        self.set_tx_env(from, to, value, data, gas_limit, gas_price, None);
+        Ok(self.transact()?.0)
+    }
+
+    fn transact(&mut self) -> Result<(ContractExecutionResult, EvmState), String> {
+        let ResultAndState { result, state } = self.evm.transact()
             .map_err(|e| format!("REVM execution failed: {:?}", e))?;
 
-        self.process_execution_result(result)
+        Ok((self.process_execution_result(result)?, state))
+    }
+
+    /// Runs an `eth_call` style message call. Without a gas price the base fee is waived so
+    /// callers without funds can still read state, as other clients do.
+    pub fn call(&mut self, request: &CallRequest) -> Result<ContractExecutionResult, String> {
+        let (gas_limit, gas_price) = self.call_gas(request);
+        self.simulate_transaction(
+            request.from.unwrap_or_default(),
+            request.to,
+            request.value,
+            request.data.clone(),
+            gas_limit,
+            gas_price,
+        )
+    }
+
+    /// Like `call`, also returning the post-state of every account the call touched.
+    pub fn call_with_state(&mut self, request: &CallRequest) -> Result<(ContractExecutionResult, EvmState), String> {
+        let (gas_limit, gas_price) = self.call_gas(request);
+        self.set_tx_env(
+            request.from.unwrap_or_default(),
+            request.to,
+            request.value,
+            request.data.clone(),
+            gas_limit,
+            gas_price,
+            None,
+        );
+        self.transact()
+    }
+
+    fn call_gas(&mut self, request: &CallRequest) -> (u64, U256) {
+        let gas_price = request.gas_price.unwrap_or_default();
+        if gas_price.is_zero() {
+            self.evm.context.evm.env.block.basefee = rU256::ZERO;
+        }
+        let gas_limit = request.gas
+            .unwrap_or_else(|| self.evm.context.evm.env.block.gas_limit.saturating_to());
+        (gas_limit, gas_price)
+    }
+
+    pub fn view_call(
+        &mut self,
+        caller: Address,
+        contract: Address,
+        calldata: Vec<u8>,
+    ) -> Result<Vec<u8>, String> {
+        let result = self.simulate_transaction(
+            caller,
+            Some(contract),
+            U256::zero(),
+            calldata,
+            1_000_000, // High gas limit for view calls
+            U256::from(20_000_000_000u64),
+        )?;
+
+        if result.success {
+            Ok(result.return_data)
+        } else {
+            Err(format!("View call failed: {}", result.reason))
+        }
     }
 
     fn process_execution_result(&self, result: ExecutionResult) -> Result<ContractExecutionResult, String> {
@@ -127,6 +420,7 @@ impl RevmExecutor {
                         topics: log.topics().iter().map(|t| H256::from_slice(&t.0)).collect(),
                         data: log.data.data.to_vec(),
                     }).collect(),
+                    outcome: ExecutionOutcome::Success,
                     reason: format!("{:?}", reason),
                     error: None,
                 })
@@ -139,36 +433,59 @@ impl RevmExecutor {
                     return_data: output.to_vec(),
                     contract_address: None,
                     logs: vec![],
+                    outcome: ExecutionOutcome::Revert,
                     reason: "Revert".to_string(),
-                    error: Some("Transaction reverted".to_string()),
+                    error: Some(match decode_revert_reason(&output) {
+                        Some(reason) => format!("execution reverted: {}", reason),
+                        None => "execution reverted".to_string(),
+                    }),
                 })
             }
+            // Like a revert, a halt is still a valid transaction: it consumed its gas and
+            // bumped the nonce, it just produced no effects.
             ExecutionResult::Halt { reason, gas_used } => {
-                Err(format!("EVM halted: {:?}, gas used: {}", reason, gas_used))
+                Ok(ContractExecutionResult {
+                    success: false,
+                    gas_used,
+                    gas_refunded: 0,
+                    return_data: vec![],
+                    contract_address: None,
+                    logs: vec![],
+                    outcome: ExecutionOutcome::Halt(reason),
+                    reason: format!("{:?}", reason),
+                    error: Some(format!("EVM halted: {:?}", reason)),
+                })
             }
         }
     }
+}
+
+impl<DB: Database + DatabaseCommit, EXT> RevmExecutor<'_, DB, EXT>
+where
+    DB::Error: Debug,
+{
+    pub fn execute_transaction(&mut self, tx: &Transaction) -> Result<ContractExecutionResult, String> {
+        self.set_transaction_env(tx);
 
-    pub fn save_state_to_world(&mut self, state: &mut WorldState) -> Result<(), String> {
-        for (address, _) in &state.accounts.clone() {
-            let evm_addr = rAddress::from_slice(address.as_bytes());
+        let result = self.evm.transact_commit()
+            .map_err(|e| format!("REVM execution failed: {:?}", e))?;
 
-            if let Ok(Some(account_info)) = self.evm.context.evm.db.basic(evm_addr) {
-                let account = state.get_account_mut(address);
-                account.balance = revm_u256_to_ethereum_u256(account_info.balance);
-                account.nonce = account_info.nonce;
+        self.process_execution_result(result)
+    }
 
-                if let Some(code) = account_info.code {
-                    account.code = code.bytes().to_vec();
-                    if !account.code.is_empty() {
-                        use sha3::{Digest, Keccak256};
-                        account.code_hash = H256::from_slice(&Keccak256::digest(&account.code));
-                    }
-                }
-            }
+    /// Like `execute_transaction` with geth's `validation: false` for simulations: the nonce
+    /// goes unchecked and a zero gas price waives the base fee.
+    pub fn execute_unvalidated_transaction(&mut self, tx: &Transaction) -> Result<ContractExecutionResult, String> {
+        self.set_transaction_env(tx);
+        self.evm.context.evm.env.tx.nonce = None;
+        if tx.gas_price.is_zero() {
+            self.evm.context.evm.env.block.basefee = rU256::ZERO;
         }
 
-        Ok(())
+        let result = self.evm.transact_commit()
+            .map_err(|e| format!("REVM execution failed: {:?}", e))?;
+
+        self.process_execution_result(result)
     }
 
     pub fn deploy_contract(
@@ -185,7 +502,7 @@ impl RevmExecutor {
         let mut deployment_data = bytecode;
         deployment_data.extend_from_slice(&constructor_args);
 
-        let result = self.execute_transaction(
+        let result = self.execute_transaction(&Transaction::new_with_gas(
             deployer,
             None,
             value,
@@ -193,7 +510,8 @@ impl RevmExecutor {
             gas_limit,
             U256::from(20_000_000_000u64), // 20 gwei
             nonce,
-        )?;
+            TransactionType::ContractDeployment,
+        ))?;
 
         if result.success {
             println!("Contract deployed successfully at: {:?}", result.contract_address);
@@ -219,7 +537,7 @@ impl RevmExecutor {
     ) -> Result<ContractExecutionResult, String> {
         println!("Calling contract {} from {} with {} bytes of calldata", contract, caller, calldata.len());
 
-        let result = self.execute_transaction(
+        let result = self.execute_transaction(&Transaction::new_with_gas(
             caller,
             Some(contract),
             value,
@@ -227,7 +545,8 @@ impl RevmExecutor {
             gas_limit,
             U256::from(20_000_000_000u64),
             nonce,
-        )?;
+            TransactionType::ContractCall,
+        ))?;
 
         if result.success {
             println!("Contract call successful");
@@ -245,28 +564,136 @@ impl RevmExecutor {
 
         Ok(result)
     }
+}
 
-    pub fn view_call(
-        &mut self,
-        caller: Address,
-        contract: Address,
-        calldata: Vec<u8>,
-    ) -> Result<Vec<u8>, String> {
-        let result = self.call_contract(
-            caller,
-            contract,
-            calldata,
-            U256::zero(),
-            1_000_000, // High gas limit for view calls
-            0, // Nonce doesn't matter for view calls
-        )?;
+fn apply_state_changes(state: &mut WorldState, changes: EvmState) {
+    for (address, evm_account) in changes {
+        if !evm_account.is_touched() {
+            continue;
+        }
 
-        if result.success {
-            Ok(result.return_data)
-        } else {
-            Err(format!("View call failed: {}", result.reason))
+        let address = Address::from_slice(address.as_slice());
+        // Storage writes below journal themselves.
+        state.journal_account(&address);
+        if evm_account.is_selfdestructed() {
+            state.journal_storage(&address);
+        }
+
+        if evm_account.is_selfdestructed() {
+            state.accounts.remove(&address);
+            continue;
+        }
+
+        // A fresh CREATE starts from empty storage even if the address held slots before.
+        if evm_account.is_created() {
+            state.wipe_storage(&address);
+        }
+
+        let account = state.accounts.entry(address).or_default();
+        account.balance = revm_u256_to_ethereum_u256(evm_account.info.balance);
+        account.nonce = evm_account.info.nonce;
+
+        if let Some(code) = &evm_account.info.code
+            && !code.is_empty()
+        {
+            account.set_code(code.original_bytes().to_vec());
+        }
+
+        for (key, slot) in evm_account.changed_storage_slots() {
+            state.write_storage(
+                &address,
+                revm_u256_to_ethereum_u256(*key),
+                revm_u256_to_ethereum_u256(slot.present_value()),
+            );
+        }
+    }
+}
+
+/// Message call parameters shared by `eth_call` and `eth_estimateGas`.
+#[derive(Debug, Clone, Default)]
+pub struct CallRequest {
+    pub from: Option<Address>,
+    pub to: Option<Address>,
+    pub value: U256,
+    pub data: Vec<u8>,
+    pub gas: Option<u64>,
+    pub gas_price: Option<U256>,
+    /// Only used by `eth_simulateV1`; plain calls skip the nonce check.
+    pub nonce: Option<u64>,
+}
+
+/// Replacement fields for one account during a call. `state` replaces the whole storage,
+/// `state_diff` only the slots it lists.
+#[derive(Debug, Clone, Default)]
+pub struct AccountOverride {
+    pub balance: Option<U256>,
+    pub nonce: Option<u64>,
+    pub code: Option<Vec<u8>>,
+    pub state: Option<HashMap<U256, U256>>,
+    pub state_diff: HashMap<U256, U256>,
+}
+
+/// Replacement fields for the block a call runs in.
+#[derive(Debug, Clone, Default)]
+pub struct BlockOverrides {
+    pub number: Option<u64>,
+    pub time: Option<u64>,
+    pub coinbase: Option<Address>,
+    pub base_fee: Option<U256>,
+}
+
+/// State and block overrides for `eth_call` and `eth_estimateGas`. They never touch the
+/// chain state itself.
+#[derive(Debug, Clone, Default)]
+pub struct CallOverrides {
+    pub accounts: HashMap<Address, AccountOverride>,
+    pub block: BlockOverrides,
+}
+
+impl CallOverrides {
+    /// `state` with the account overrides layered on top; only overridden accounts are cached.
+    pub fn overlay<'a>(&self, state: &'a WorldState) -> CacheDB<&'a WorldState> {
+        let mut db = CacheDB::new(state);
+        self.apply_to_overlay(&mut db);
+        db
+    }
+
+    /// Layers the account overrides on top of an existing overlay, such as one a simulation
+    /// has been committing to.
+    pub fn apply_to_overlay<S: DatabaseRef<Error = Infallible>>(&self, db: &mut CacheDB<S>) {
+        for (address, account_override) in &self.accounts {
+            let Ok(account) = db.load_account(rAddress::from_slice(address.as_bytes()));
+            if account.account_state == DbAccountState::NotExisting {
+                account.account_state = DbAccountState::Touched;
+            }
+            if let Some(balance) = account_override.balance {
+                account.info.balance = ethereum_u256_to_revm_u256(balance);
+            }
+            if let Some(nonce) = account_override.nonce {
+                account.info.nonce = nonce;
+            }
+            if let Some(code) = &account_override.code {
+                let bytecode = Bytecode::new_raw(Bytes::from(code.clone()));
+                account.info.code_hash = bytecode.hash_slow();
+                account.info.code = Some(bytecode);
+            }
+            if account_override.state.is_some() {
+                account.account_state = DbAccountState::StorageCleared;
+                account.storage.clear();
+            }
+            for (key, value) in account_override.state.iter().flatten().chain(&account_override.state_diff) {
+                account.storage.insert(ethereum_u256_to_revm_u256(*key), ethereum_u256_to_revm_u256(*value));
+            }
         }
     }
+
+    pub fn apply_to_block(&self, block: &mut Block) {
+        let overrides = &self.block;
+        block.number = overrides.number.unwrap_or(block.number);
+        block.timestamp = overrides.time.unwrap_or(block.timestamp);
+        block.miner = overrides.coinbase.unwrap_or(block.miner);
+        block.base_fee_per_gas = overrides.base_fee.unwrap_or(block.base_fee_per_gas);
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -277,10 +704,29 @@ pub struct ContractExecutionResult {
     pub return_data: Vec<u8>,
     pub contract_address: Option<Address>,
     pub logs: Vec<EvmLog>,
+    pub outcome: ExecutionOutcome,
     pub reason: String,
     pub error: Option<String>,
 }
 
+impl ContractExecutionResult {
+    pub fn is_revert(&self) -> bool {
+        self.outcome == ExecutionOutcome::Revert
+    }
+
+    pub fn is_out_of_gas(&self) -> bool {
+        matches!(self.outcome, ExecutionOutcome::Halt(HaltReason::OutOfGas(_)))
+    }
+}
+
+/// How an execution ended: a revert hands back data and unused gas, a halt consumes all gas.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExecutionOutcome {
+    Success,
+    Revert,
+    Halt(HaltReason),
+}
+
 #[derive(Debug, Clone)]
 pub struct EvmLog {
     pub address: Address,
@@ -394,14 +840,475 @@ impl SolidityContracts {
     }
 }
 
+/// Options of geth's struct logger, as accepted by `debug_traceTransaction`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StructLoggerConfig {
+    pub enable_memory: bool,
+    pub disable_stack: bool,
+    pub disable_storage: bool,
+    pub enable_return_data: bool,
+}
+
+/// One executed opcode, with the machine state from just before it ran.
+#[derive(Debug, Clone, PartialEq)]
+pub struct StructLog {
+    pub pc: u64,
+    pub op: String,
+    pub gas: u64,
+    pub gas_cost: u64,
+    pub depth: u64,
+    pub refund: u64,
+    pub stack: Option<Vec<U256>>,
+    /// Memory as 32-byte words.
+    pub memory: Option<Vec<H256>>,
+    pub return_data: Option<Vec<u8>>,
+    /// Storage of the executing contract seen so far, reported on SLOAD and SSTORE.
+    pub storage: Option<BTreeMap<H256, H256>>,
+    pub error: Option<String>,
+}
+
+/// Inspector producing geth-style struct logs.
+#[derive(Debug, Default)]
+pub struct StructLogger {
+    config: StructLoggerConfig,
+    logs: Vec<StructLog>,
+    storage: HashMap<Address, BTreeMap<H256, H256>>,
+    /// The step being executed, completed in `step_end` once its cost is known.
+    current: Option<StructLog>,
+    /// Slot read by the current SLOAD, whose value is only on the stack after it ran.
+    pending_load: Option<(Address, H256)>,
+}
+
+impl StructLogger {
+    pub fn new(config: StructLoggerConfig) -> Self {
+        StructLogger { config, ..Default::default() }
+    }
+
+    pub fn into_logs(self) -> Vec<StructLog> {
+        self.logs
+    }
+
+    fn record_storage(&mut self, address: Address, key: H256, value: H256) -> BTreeMap<H256, H256> {
+        let storage = self.storage.entry(address).or_default();
+        storage.insert(key, value);
+        storage.clone()
+    }
+}
+
+impl<DB: Database> Inspector<DB> for StructLogger {
+    fn step(&mut self, interp: &mut Interpreter, context: &mut EvmContext<DB>) {
+        let opcode = interp.current_opcode();
+        let stack = interp.stack.data();
+
+        let mut log = StructLog {
+            pc: interp.program_counter() as u64,
+            op: OpCode::new(opcode).map_or_else(|| format!("opcode 0x{:02x} not defined", opcode), |op| op.as_str().to_string()),
+            gas: interp.gas.remaining(),
+            gas_cost: 0,
+            depth: context.journaled_state.depth(),
+            refund: interp.gas.refunded().max(0) as u64,
+            stack: (!self.config.disable_stack)
+                .then(|| stack.iter().copied().map(revm_u256_to_ethereum_u256).collect()),
+            memory: self.config.enable_memory
+                .then(|| interp.shared_memory.context_memory().chunks(32).map(H256::from_slice).collect()),
+            return_data: self.config.enable_return_data.then(|| interp.return_data_buffer.to_vec()),
+            storage: None,
+            error: None,
+        };
+
+        if !self.config.disable_storage {
+            let address = Address::from_slice(interp.contract.target_address.as_slice());
+            if opcode == OpCode::SSTORE.get() && stack.len() >= 2 {
+                let (key, value) = (revm_u256_to_h256(stack[stack.len() - 1]), revm_u256_to_h256(stack[stack.len() - 2]));
+                log.storage = Some(self.record_storage(address, key, value));
+            } else if opcode == OpCode::SLOAD.get() && !stack.is_empty() {
+                self.pending_load = Some((address, revm_u256_to_h256(stack[stack.len() - 1])));
+            }
+        }
+
+        self.current = Some(log);
+    }
+
+    fn step_end(&mut self, interp: &mut Interpreter, _context: &mut EvmContext<DB>) {
+        let Some(mut log) = self.current.take() else {
+            return;
+        };
+
+        log.gas_cost = log.gas.saturating_sub(interp.gas.remaining());
+        if interp.instruction_result.is_error() {
+            log.error = Some(halt_message(interp.instruction_result));
+        }
+
+        if let Some((address, key)) = self.pending_load.take()
+            && let Ok(value) = interp.stack.peek(0)
+        {
+            log.storage = Some(self.record_storage(address, key, revm_u256_to_h256(value)));
+        }
+
+        self.logs.push(log);
+    }
+}
+
+/// One frame of geth's `callTracer` output.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CallFrame {
+    pub call_type: String,
+    pub from: Address,
+    pub to: Option<Address>,
+    /// `None` for DELEGATECALL and STATICCALL, which move no value.
+    pub value: Option<U256>,
+    pub gas: u64,
+    pub gas_used: u64,
+    pub input: Vec<u8>,
+    pub output: Vec<u8>,
+    pub error: Option<String>,
+    pub revert_reason: Option<String>,
+    pub calls: Vec<CallFrame>,
+}
+
+/// Inspector building the tree of message calls and contract creations.
+#[derive(Debug, Default)]
+pub struct CallTracer {
+    /// Frames still executing, innermost last.
+    stack: Vec<CallFrame>,
+    root: Option<CallFrame>,
+    gas_limit: u64,
+}
+
+impl CallTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The finished call tree. Like geth, the outermost frame reports the transaction's gas
+    /// limit and `gas_used`, its total gas including intrinsic gas and refunds.
+    pub fn into_call_frame(self, gas_used: u64) -> Option<CallFrame> {
+        let gas_limit = self.gas_limit;
+        self.root.map(|root| CallFrame { gas: gas_limit, gas_used, ..root })
+    }
+
+    fn enter<DB: Database>(&mut self, context: &EvmContext<DB>, frame: CallFrame) {
+        if self.stack.is_empty() {
+            self.gas_limit = context.env.tx.gas_limit;
+        }
+        self.stack.push(frame);
+    }
+
+    fn exit(&mut self, result: &InterpreterResult, created: Option<rAddress>) {
+        let Some(mut frame) = self.stack.pop() else {
+            return;
+        };
+
+        frame.gas_used = result.gas.spent();
+        if let Some(address) = created {
+            frame.to = Some(Address::from_slice(address.as_slice()));
+        }
+        if result.result.is_revert() {
+            frame.output = result.output.to_vec();
+            frame.error = Some("execution reverted".to_string());
+            frame.revert_reason = decode_revert_reason(&result.output);
+        } else if result.result.is_error() {
+            frame.error = Some(halt_message(result.result));
+        } else {
+            frame.output = result.output.to_vec();
+        }
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.calls.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+}
+
+/// geth's wording for the common ways a frame can halt.
+fn halt_message(result: InstructionResult) -> String {
+    match result {
+        InstructionResult::OutOfGas
+        | InstructionResult::MemoryOOG
+        | InstructionResult::MemoryLimitOOG
+        | InstructionResult::PrecompileOOG
+        | InstructionResult::InvalidOperandOOG => "out of gas".to_string(),
+        InstructionResult::OpcodeNotFound | InstructionResult::InvalidEFOpcode => "invalid opcode".to_string(),
+        InstructionResult::InvalidJump => "invalid jump destination".to_string(),
+        InstructionResult::StackUnderflow => "stack underflow".to_string(),
+        InstructionResult::StackOverflow => "stack limit reached 1024".to_string(),
+        InstructionResult::OutOfFunds => "insufficient balance for transfer".to_string(),
+        other => format!("{:?}", other),
+    }
+}
+
+impl<DB: Database> Inspector<DB> for CallTracer {
+    fn call(&mut self, context: &mut EvmContext<DB>, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        // For DELEGATECALL and CALLCODE the calling contract is the storage context.
+        let (call_type, from, value) = match inputs.scheme {
+            CallScheme::Call => ("CALL", inputs.caller, Some(inputs.call_value())),
+            CallScheme::CallCode => ("CALLCODE", inputs.target_address, Some(inputs.call_value())),
+            CallScheme::DelegateCall => ("DELEGATECALL", inputs.target_address, None),
+            CallScheme::StaticCall => ("STATICCALL", inputs.caller, None),
+        };
+
+        self.enter(context, CallFrame {
+            call_type: call_type.to_string(),
+            from: Address::from_slice(from.as_slice()),
+            to: Some(Address::from_slice(inputs.bytecode_address.as_slice())),
+            value: value.map(revm_u256_to_ethereum_u256),
+            gas: inputs.gas_limit,
+            gas_used: 0,
+            input: inputs.input.to_vec(),
+            output: vec![],
+            error: None,
+            revert_reason: None,
+            calls: vec![],
+        });
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut EvmContext<DB>, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        self.exit(&outcome.result, None);
+        outcome
+    }
+
+    fn create(&mut self, context: &mut EvmContext<DB>, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        let call_type = match inputs.scheme {
+            CreateScheme::Create => "CREATE",
+            CreateScheme::Create2 { .. } => "CREATE2",
+        };
+
+        self.enter(context, CallFrame {
+            call_type: call_type.to_string(),
+            from: Address::from_slice(inputs.caller.as_slice()),
+            to: None,
+            value: Some(revm_u256_to_ethereum_u256(inputs.value)),
+            gas: inputs.gas_limit,
+            gas_used: 0,
+            input: inputs.init_code.to_vec(),
+            output: vec![],
+            error: None,
+            revert_reason: None,
+            calls: vec![],
+        });
+        None
+    }
+
+    fn create_end(&mut self, _context: &mut EvmContext<DB>, _inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        self.exit(&outcome.result, outcome.address);
+        outcome
+    }
+}
+
+/// Kinds of internal operation, numbered as Otterscan's `ots_getInternalOperations` reports them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum InternalOperationKind {
+    Transfer = 0,
+    SelfDestruct = 1,
+    Create = 2,
+    Create2 = 3,
+}
+
+/// Value moved or a contract created by contract code rather than by the transaction itself.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InternalOperation {
+    pub kind: InternalOperationKind,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+}
+
+/// Inspector collecting a transaction's internal operations. Operations inside frames that
+/// revert or halt are dropped, since their effects are undone.
+#[derive(Debug, Default)]
+pub struct InternalOperationTracer {
+    /// Operations of each frame still executing, innermost last.
+    stack: Vec<Vec<InternalOperation>>,
+    operations: Vec<InternalOperation>,
+}
+
+impl InternalOperationTracer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn into_operations(self) -> Vec<InternalOperation> {
+        self.operations
+    }
+
+    /// Opens a frame, starting with `operation` unless the frame is the transaction itself.
+    fn enter(&mut self, operation: InternalOperation) {
+        let internal = !self.stack.is_empty();
+        self.stack.push(if internal { vec![operation] } else { vec![] });
+    }
+
+    fn exit(&mut self, result: &InterpreterResult) {
+        let Some(operations) = self.stack.pop() else {
+            return;
+        };
+        if !result.result.is_ok() {
+            return;
+        }
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.extend(operations),
+            None => self.operations.extend(operations),
+        }
+    }
+}
+
+impl<DB: Database> Inspector<DB> for InternalOperationTracer {
+    fn call(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CallInputs) -> Option<CallOutcome> {
+        let value = inputs.transfer_value().filter(|value| !value.is_zero());
+        match value {
+            // CALLCODE and DELEGATECALL keep any value within the calling contract.
+            Some(value) if inputs.scheme == CallScheme::Call => self.enter(InternalOperation {
+                kind: InternalOperationKind::Transfer,
+                from: Address::from_slice(inputs.caller.as_slice()),
+                to: Address::from_slice(inputs.target_address.as_slice()),
+                value: revm_u256_to_ethereum_u256(value),
+            }),
+            _ => self.stack.push(vec![]),
+        }
+        None
+    }
+
+    fn call_end(&mut self, _context: &mut EvmContext<DB>, _inputs: &CallInputs, outcome: CallOutcome) -> CallOutcome {
+        self.exit(&outcome.result);
+        outcome
+    }
+
+    fn create(&mut self, _context: &mut EvmContext<DB>, inputs: &mut CreateInputs) -> Option<CreateOutcome> {
+        let kind = match inputs.scheme {
+            CreateScheme::Create => InternalOperationKind::Create,
+            CreateScheme::Create2 { .. } => InternalOperationKind::Create2,
+        };
+
+        // The new address is only known once the creation returns.
+        self.enter(InternalOperation {
+            kind,
+            from: Address::from_slice(inputs.caller.as_slice()),
+            to: Address::zero(),
+            value: revm_u256_to_ethereum_u256(inputs.value),
+        });
+        None
+    }
+
+    fn create_end(&mut self, _context: &mut EvmContext<DB>, _inputs: &CreateInputs, outcome: CreateOutcome) -> CreateOutcome {
+        // An internal creation is the first operation of its own frame.
+        if self.stack.len() > 1
+            && let Some(address) = outcome.address
+            && let Some(creation) = self.stack.last_mut().and_then(|operations| operations.first_mut())
+        {
+            creation.to = Address::from_slice(address.as_slice());
+        }
+        self.exit(&outcome.result);
+        outcome
+    }
+
+    fn selfdestruct(&mut self, contract: rAddress, target: rAddress, value: rU256) {
+        if let Some(operations) = self.stack.last_mut() {
+            operations.push(InternalOperation {
+                kind: InternalOperationKind::SelfDestruct,
+                from: Address::from_slice(contract.as_slice()),
+                to: Address::from_slice(target.as_slice()),
+                value: revm_u256_to_ethereum_u256(value),
+            });
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AccountState {
+    pub balance: U256,
+    pub nonce: u64,
+    pub code: Vec<u8>,
+    /// Only the slots the execution accessed.
+    pub storage: BTreeMap<H256, H256>,
+}
+
+impl AccountState {
+    /// Whether this is how an account that does not exist looks.
+    pub fn is_empty(&self) -> bool {
+        self.balance.is_zero() && self.nonce == 0 && self.code.is_empty() && self.storage.values().all(H256::is_zero)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct AccountDiff {
+    pub pre: AccountState,
+    pub post: AccountState,
+}
+
+impl AccountDiff {
+    pub fn is_modified(&self) -> bool {
+        self.pre != self.post
+    }
+
+    /// Slots whose value the execution changed.
+    pub fn changed_slots(&self) -> impl Iterator<Item = &H256> {
+        self.post.storage.iter()
+            .filter(|(key, value)| self.pre.storage.get(*key) != Some(*value))
+            .map(|(key, _)| key)
+    }
+}
+
+/// Every account an execution touched, before and after it: the data behind geth's
+/// `prestateTracer`. It is taken from revm's uncommitted post-state rather than from inspector
+/// hooks, since gas payments and the miner tip are settled after the last hook fires.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StateDiff {
+    pub accounts: BTreeMap<Address, AccountDiff>,
+}
+
+impl StateDiff {
+    /// Compares `changes` against `state`, the state the execution started from.
+    pub fn new(state: &WorldState, changes: &EvmState) -> Self {
+        let accounts = changes.iter()
+            .map(|(address, account)| {
+                let address = Address::from_slice(address.as_slice());
+                let pre_account = state.get_account(&address);
+
+                let mut pre = AccountState {
+                    balance: pre_account.map(|account| account.balance).unwrap_or_default(),
+                    nonce: pre_account.map(|account| account.nonce).unwrap_or_default(),
+                    code: pre_account.map(|account| account.code.clone()).unwrap_or_default(),
+                    storage: BTreeMap::new(),
+                };
+                let mut post = AccountState {
+                    balance: revm_u256_to_ethereum_u256(account.info.balance),
+                    nonce: account.info.nonce,
+                    code: account.info.code.as_ref()
+                        .map_or_else(|| pre.code.clone(), |code| code.original_bytes().to_vec()),
+                    storage: BTreeMap::new(),
+                };
+                for (key, slot) in &account.storage {
+                    let key = revm_u256_to_h256(*key);
+                    pre.storage.insert(key, revm_u256_to_h256(slot.original_value()));
+                    post.storage.insert(key, revm_u256_to_h256(slot.present_value()));
+                }
+
+                (address, AccountDiff { pre, post })
+            })
+            .collect();
+
+        StateDiff { accounts }
+    }
+}
+
+/// Outcome of a traced execution: its result, the inspector that watched it and what it did
+/// to the state. Nothing is committed.
+#[derive(Debug)]
+pub struct Trace<I> {
+    pub result: ContractExecutionResult,
+    pub state_diff: StateDiff,
+    pub inspector: I,
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::account::WorldState;
+    use revm::db::WrapDatabaseRef;
 
     #[test]
     fn test_revm_creation() {
-        let executor = RevmExecutor::new(1, 1234567890, Address::from([1u8; 20]), 9000_000_000_000_000_000);
+        let executor = RevmExecutor::new(WorldState::new(), &Block::new(1, H256::zero(), vec![]), 1337, SpecId::CANCUN);
         assert!(true);
     }
 
@@ -427,6 +1334,95 @@ mod tests {
         assert_ne!(addr, Address::zero());
     }
 
+    #[test]
+    fn test_deployment_persists_code_and_storage() {
+        let deployer = Address::from([1u8; 20]);
+        let mut state = WorldState::new();
+        state.set_balance(&deployer, U256::from(10u64).pow(U256::from(18)));
+
+        // Constructor stores 42 in slot 0, runtime returns slot 0.
+        let runtime = hex::decode("60005460005260206000f3").unwrap();
+        let initcode = [hex::decode("602a600055600b6011600039600b6000f3").unwrap(), runtime.clone()].concat();
+
+        let mut executor = RevmExecutor::new(&mut state, &Block::new(1, H256::zero(), vec![]), 1337, SpecId::CANCUN);
+        let result = executor.execute_transaction(&Transaction::new_with_gas(
+            deployer, None, U256::zero(), initcode, 1_000_000, U256::from(1_000_000_000u64), 0,
+            TransactionType::ContractDeployment,
+        )).unwrap();
+        drop(executor);
+
+        assert!(result.success);
+        let contract = ContractUtils::calculate_create_address(&deployer, 0);
+        assert_eq!(result.contract_address, Some(contract));
+        assert_eq!(state.get_storage(&contract, &U256::zero()), U256::from(42));
+        assert_eq!(state.get_contract_code(&contract), runtime);
+        assert_eq!(state.get_nonce(&deployer), 1);
+    }
+
+    #[test]
+    fn test_nested_create_persists_child_account() {
+        let deployer = Address::from([1u8; 20]);
+        let mut state = WorldState::new();
+        state.set_balance(&deployer, U256::from(10u64).pow(U256::from(18)));
+
+        // Constructor CREATEs a child whose runtime code is a single STOP byte.
+        let initcode = hex::decode("6460016000f36000526005601b6000f000").unwrap();
+
+        let mut executor = RevmExecutor::new(&mut state, &Block::new(1, H256::zero(), vec![]), 1337, SpecId::CANCUN);
+        let result = executor.execute_transaction(&Transaction::new_with_gas(
+            deployer, None, U256::zero(), initcode, 1_000_000, U256::from(1_000_000_000u64), 0,
+            TransactionType::ContractDeployment,
+        )).unwrap();
+        drop(executor);
+
+        assert!(result.success);
+        let factory = ContractUtils::calculate_create_address(&deployer, 0);
+        let child = ContractUtils::calculate_create_address(&factory, 1);
+        assert_eq!(state.get_nonce(&factory), 2);
+        assert_eq!(state.get_contract_code(&child), vec![0x00]);
+    }
+
+    #[test]
+    fn test_world_state_database_reads() {
+        let alice = Address::from([1u8; 20]);
+        let contract = Address::from([2u8; 20]);
+        let mut state = WorldState::new();
+        state.set_balance(&alice, U256::from(1000));
+        state.deploy_contract(&alice, &contract, vec![0x60, 0x00]).unwrap();
+        state.set_storage(&contract, U256::from(7), U256::from(99));
+
+        let eoa = state.basic_ref(rAddress::from_slice(alice.as_bytes())).unwrap().unwrap();
+        assert_eq!(eoa.balance, rU256::from(1000));
+        assert_eq!(eoa.nonce, 1);
+        assert_eq!(eoa.code_hash, KECCAK_EMPTY);
+
+        let code = state.basic_ref(rAddress::from_slice(contract.as_bytes())).unwrap().unwrap();
+        assert_eq!(code.code.unwrap().original_bytes().to_vec(), vec![0x60, 0x00]);
+
+        let slot = state.storage_ref(rAddress::from_slice(contract.as_bytes()), rU256::from(7)).unwrap();
+        assert_eq!(slot, rU256::from(99));
+        assert!(state.basic_ref(rAddress::from_slice(&[9u8; 20])).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_simulation_leaves_state_untouched() {
+        let caller = Address::from([1u8; 20]);
+        let mut state = WorldState::new();
+        state.set_balance(&caller, U256::from(10u64).pow(U256::from(18)));
+        let root_before = state.get_state_root();
+
+        let mut executor = RevmExecutor::new(WrapDatabaseRef(&state), &Block::new(1, H256::zero(), vec![]), 1337, SpecId::CANCUN);
+        let result = executor.simulate_transaction(
+            caller, Some(Address::from([2u8; 20])), U256::from(5), vec![], 21_000, U256::from(1_000_000_000u64),
+        ).unwrap();
+        drop(executor);
+
+        assert!(result.success);
+        assert_eq!(state.get_balance(&Address::from([2u8; 20])), U256::zero());
+        assert_eq!(state.get_nonce(&caller), 0);
+        assert_eq!(state.get_state_root(), root_before);
+    }
+
     #[test]
     fn test_function_encoding() {
         let set_call = SolidityContracts::encode_set_call(U256::from(42));
@@ -435,4 +1431,125 @@ mod tests {
         let get_call = SolidityContracts::encode_get_call();
         assert_eq!(get_call.len(), 4);
     }
+
+    #[test]
+    fn test_struct_logger_records_steps_and_storage() {
+        let contract = Address::from([0x42u8; 20]);
+        let mut state = WorldState::new();
+        // PUSH1 7, PUSH1 1, SSTORE, PUSH1 1, SLOAD, STOP
+        state.get_account_mut(&contract).set_code(hex::decode("600760015560015400").unwrap());
+
+        let config = StructLoggerConfig { enable_memory: true, ..Default::default() };
+        let block = Block::new(1, H256::zero(), vec![]);
+        let mut executor = RevmExecutor::new_with_inspector(&mut state, &block, 1337, SpecId::CANCUN, StructLogger::new(config));
+        let request = CallRequest { to: Some(contract), gas: Some(100_000), ..Default::default() };
+        assert!(executor.call(&request).unwrap().success);
+
+        let logs = executor.into_inspector().into_logs();
+        let ops: Vec<&str> = logs.iter().map(|log| log.op.as_str()).collect();
+        assert_eq!(ops, vec!["PUSH1", "PUSH1", "SSTORE", "PUSH1", "SLOAD", "STOP"]);
+
+        assert_eq!(logs[0].depth, 1);
+        assert_eq!(logs[0].gas_cost, 3);
+        assert_eq!(logs[1].gas, logs[0].gas - 3);
+        assert_eq!(logs[2].stack, Some(vec![U256::from(7), U256::from(1)]));
+        assert_eq!(logs[2].gas_cost, 22_100);
+
+        let expected = BTreeMap::from([(H256::from_low_u64_be(1), H256::from_low_u64_be(7))]);
+        assert_eq!(logs[2].storage, Some(expected.clone()));
+        assert_eq!(logs[4].storage, Some(expected));
+        assert_eq!(logs[4].gas_cost, 100);
+        assert_eq!(logs[5].memory, Some(vec![]));
+    }
+
+    #[test]
+    fn test_struct_logger_reports_halts_like_geth() {
+        let contract = Address::from([0x42u8; 20]);
+        let mut state = WorldState::new();
+        // PUSH1 1, JUMP: the target is not a JUMPDEST.
+        state.get_account_mut(&contract).set_code(hex::decode("600156").unwrap());
+
+        let block = Block::new(1, H256::zero(), vec![]);
+        let logger = StructLogger::new(StructLoggerConfig::default());
+        let mut executor = RevmExecutor::new_with_inspector(&mut state, &block, 1337, SpecId::CANCUN, logger);
+        let request = CallRequest { to: Some(contract), gas: Some(100_000), ..Default::default() };
+        assert!(!executor.call(&request).unwrap().success);
+
+        let logs = executor.into_inspector().into_logs();
+        assert_eq!(logs.last().unwrap().error, Some("invalid jump destination".to_string()));
+    }
+
+    #[test]
+    fn test_call_tracer_and_state_diff() {
+        let outer = Address::from([0x42u8; 20]);
+        let inner = Address::from([0x43u8; 20]);
+        let caller = Address::from([0x11u8; 20]);
+        let mut state = WorldState::new();
+        // SSTORE(0, 5), then CALL(gas, inner, 0, 0, 0, 0, 0) and STOP
+        let outer_code = "6005600055600060006000600060007343434343434343434343434343434343434343435af100";
+        state.get_account_mut(&outer).set_code(hex::decode(outer_code).unwrap());
+        // REVERT(0, 0)
+        state.get_account_mut(&inner).set_code(hex::decode("60006000fd").unwrap());
+        let pre_state = state.clone();
+
+        let block = Block::new(1, H256::zero(), vec![]);
+        let mut executor = RevmExecutor::new_with_inspector(&mut state, &block, 1337, SpecId::CANCUN, CallTracer::new());
+        let request = CallRequest { from: Some(caller), to: Some(outer), gas: Some(100_000), ..Default::default() };
+        let (result, changes) = executor.call_with_state(&request).unwrap();
+        assert!(result.success);
+
+        let root = executor.into_inspector().into_call_frame(result.gas_used).unwrap();
+        assert_eq!(root.call_type, "CALL");
+        assert_eq!((root.from, root.to), (caller, Some(outer)));
+        assert_eq!((root.gas, root.gas_used), (100_000, result.gas_used));
+        assert_eq!(root.error, None);
+        assert_eq!(root.calls.len(), 1);
+
+        let nested = &root.calls[0];
+        assert_eq!((nested.from, nested.to, nested.value), (outer, Some(inner), Some(U256::zero())));
+        assert_eq!(nested.error, Some("execution reverted".to_string()));
+        assert!(nested.calls.is_empty());
+
+        let diff = StateDiff::new(&pre_state, &changes);
+        let outer_diff = &diff.accounts[&outer];
+        assert!(outer_diff.is_modified());
+        assert_eq!(outer_diff.changed_slots().collect::<Vec<_>>(), vec![&H256::zero()]);
+        assert_eq!(outer_diff.post.storage[&H256::zero()], H256::from_low_u64_be(5));
+        assert!(!diff.accounts[&inner].is_modified());
+        assert!(diff.accounts[&caller].pre.is_empty());
+        assert!(!outer_diff.pre.is_empty());
+    }
+
+    #[test]
+    fn test_internal_operation_tracer_skips_reverted_frames() {
+        let outer = Address::from([0x42u8; 20]);
+        let inner = Address::from([0x43u8; 20]);
+        let bob = Address::from([0xb0u8; 20]);
+        let mut state = WorldState::new();
+        // CALL bob with 7 wei, CALL inner with 3 wei, then CREATE an empty contract with 2 wei.
+        let outer_code = "6000600060006000600773b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b0b05af150\
+                          600060006000600060037343434343434343434343434343434343434343435af150\
+                          600060006002f05000";
+        state.get_account_mut(&outer).set_code(hex::decode(outer_code).unwrap());
+        state.get_account_mut(&outer).balance = U256::from(100);
+        // REVERT(0, 0)
+        state.get_account_mut(&inner).set_code(hex::decode("60006000fd").unwrap());
+
+        let block = Block::new(1, H256::zero(), vec![]);
+        let tracer = InternalOperationTracer::new();
+        let mut executor = RevmExecutor::new_with_inspector(&mut state, &block, 1337, SpecId::CANCUN, tracer);
+        let request = CallRequest { to: Some(outer), gas: Some(200_000), ..Default::default() };
+        assert!(executor.call(&request).unwrap().success);
+
+        let operations = executor.into_inspector().into_operations();
+        assert_eq!(operations, vec![
+            InternalOperation { kind: InternalOperationKind::Transfer, from: outer, to: bob, value: U256::from(7) },
+            InternalOperation {
+                kind: InternalOperationKind::Create,
+                from: outer,
+                to: ContractUtils::calculate_create_address(&outer, 0),
+                value: U256::from(2),
+            },
+        ]);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,192 @@
+use revm::primitives::SpecId;
+use serde::{Deserialize, Serialize};
+
+/// Forks the node can run. The chain starts with EIP-1559 base fees, so London is the floor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Hardfork {
+    London,
+    Paris,
+    Shanghai,
+    Cancun,
+    Prague,
+}
+
+impl Hardfork {
+    pub const ALL: [Hardfork; 5] = [
+        Hardfork::London,
+        Hardfork::Paris,
+        Hardfork::Shanghai,
+        Hardfork::Cancun,
+        Hardfork::Prague,
+    ];
+
+    pub fn spec_id(self) -> SpecId {
+        match self {
+            Hardfork::London => SpecId::LONDON,
+            Hardfork::Paris => SpecId::MERGE,
+            Hardfork::Shanghai => SpecId::SHANGHAI,
+            Hardfork::Cancun => SpecId::CANCUN,
+            Hardfork::Prague => SpecId::PRAGUE,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Hardfork::London => "london",
+            Hardfork::Paris => "paris",
+            Hardfork::Shanghai => "shanghai",
+            Hardfork::Cancun => "cancun",
+            Hardfork::Prague => "prague",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|fork| fork.name().eq_ignore_ascii_case(name))
+    }
+}
+
+/// When a fork turns on: at a block number (pre-merge style) or at a block timestamp.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ForkCondition {
+    Block(u64),
+    Timestamp(u64),
+}
+
+impl ForkCondition {
+    pub fn is_active(&self, number: u64, timestamp: u64) -> bool {
+        match *self {
+            ForkCondition::Block(activation) => number >= activation,
+            ForkCondition::Timestamp(activation) => timestamp >= activation,
+        }
+    }
+}
+
+/// Activation of each scheduled fork, in fork order. Forks missing from the schedule never
+/// activate, and London is always active.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ForkSchedule {
+    forks: Vec<(Hardfork, ForkCondition)>,
+}
+
+impl ForkSchedule {
+    /// Everything up to Cancun active from genesis.
+    pub fn new() -> Self {
+        Self::new_with_forks(vec![
+            (Hardfork::Paris, ForkCondition::Block(0)),
+            (Hardfork::Shanghai, ForkCondition::Block(0)),
+            (Hardfork::Cancun, ForkCondition::Block(0)),
+        ]).unwrap()
+    }
+
+    /// Only `fork` and the forks before it, all active from genesis.
+    pub fn new_at(fork: Hardfork) -> Self {
+        Self::new_with_forks(Hardfork::ALL.into_iter()
+            .filter(|scheduled| *scheduled <= fork)
+            .map(|scheduled| (scheduled, ForkCondition::Block(0)))
+            .collect()).unwrap()
+    }
+
+    /// Builds a schedule from activations listed in fork order. Each fork needs the one before
+    /// it, and a fork cannot activate before its predecessor.
+    pub fn new_with_forks(mut forks: Vec<(Hardfork, ForkCondition)>) -> Result<Self, String> {
+        if forks.first().map(|(fork, _)| *fork) != Some(Hardfork::London) {
+            forks.insert(0, (Hardfork::London, ForkCondition::Block(0)));
+        }
+        if forks[0].1 != ForkCondition::Block(0) {
+            return Err("London must be active from genesis".to_string());
+        }
+
+        for (index, pair) in forks.windows(2).enumerate() {
+            let ((previous, previous_condition), (fork, condition)) = (pair[0], pair[1]);
+            if fork != Hardfork::ALL[index + 1] {
+                return Err(format!("{} scheduled without {}", fork.name(), Hardfork::ALL[index + 1].name()));
+            }
+
+            let out_of_order = match (previous_condition, condition) {
+                (ForkCondition::Block(a), ForkCondition::Block(b)) => b < a,
+                (ForkCondition::Timestamp(a), ForkCondition::Timestamp(b)) => b < a,
+                (ForkCondition::Timestamp(_), ForkCondition::Block(_)) => true,
+                (ForkCondition::Block(_), ForkCondition::Timestamp(_)) => false,
+            };
+            if out_of_order {
+                return Err(format!("{} activates before {}", fork.name(), previous.name()));
+            }
+        }
+
+        Ok(ForkSchedule { forks })
+    }
+
+    pub fn forks(&self) -> &[(Hardfork, ForkCondition)] {
+        &self.forks
+    }
+
+    /// The newest fork active for a block with this number and timestamp. A fork never
+    /// activates ahead of its predecessor, even when its own condition is already met.
+    pub fn hardfork_at(&self, number: u64, timestamp: u64) -> Hardfork {
+        self.forks.iter()
+            .take_while(|(_, condition)| condition.is_active(number, timestamp))
+            .last()
+            .map(|(fork, _)| *fork)
+            .unwrap_or(Hardfork::London)
+    }
+
+    pub fn is_active(&self, fork: Hardfork, number: u64, timestamp: u64) -> bool {
+        self.hardfork_at(number, timestamp) >= fork
+    }
+
+    /// The first scheduled fork not yet active at this block, if any.
+    pub fn next_fork(&self, number: u64, timestamp: u64) -> Option<(Hardfork, ForkCondition)> {
+        self.forks.iter()
+            .find(|(_, condition)| !condition.is_active(number, timestamp))
+            .copied()
+    }
+}
+
+impl Default for ForkSchedule {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_schedule_runs_cancun() {
+        let schedule = ForkSchedule::new();
+        assert_eq!(schedule.hardfork_at(0, 0), Hardfork::Cancun);
+        assert_eq!(schedule.hardfork_at(100, 1_700_000_000).spec_id(), SpecId::CANCUN);
+        assert_eq!(schedule.next_fork(100, 1_700_000_000), None);
+        assert!(!schedule.is_active(Hardfork::Prague, 100, 1_700_000_000));
+    }
+
+    #[test]
+    fn test_forks_activate_by_block_and_timestamp() {
+        let schedule = ForkSchedule::new_with_forks(vec![
+            (Hardfork::Paris, ForkCondition::Block(0)),
+            (Hardfork::Shanghai, ForkCondition::Block(10)),
+            (Hardfork::Cancun, ForkCondition::Timestamp(1_000)),
+        ]).unwrap();
+
+        assert_eq!(schedule.hardfork_at(9, 2_000), Hardfork::Paris);
+        assert_eq!(schedule.hardfork_at(10, 999), Hardfork::Shanghai);
+        assert_eq!(schedule.hardfork_at(10, 1_000), Hardfork::Cancun);
+        assert_eq!(schedule.next_fork(10, 999), Some((Hardfork::Cancun, ForkCondition::Timestamp(1_000))));
+    }
+
+    #[test]
+    fn test_invalid_schedules_are_rejected() {
+        let skipped = ForkSchedule::new_with_forks(vec![(Hardfork::Shanghai, ForkCondition::Block(0))]);
+        assert_eq!(skipped, Err("shanghai scheduled without paris".to_string()));
+
+        let reversed = ForkSchedule::new_with_forks(vec![
+            (Hardfork::Paris, ForkCondition::Block(5)),
+            (Hardfork::Shanghai, ForkCondition::Block(2)),
+        ]);
+        assert_eq!(reversed, Err("shanghai activates before paris".to_string()));
+
+        assert_eq!(Hardfork::from_name("Cancun"), Some(Hardfork::Cancun));
+        assert_eq!(ForkSchedule::new_at(Hardfork::Shanghai).hardfork_at(0, 0), Hardfork::Shanghai);
+    }
+}
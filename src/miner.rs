@@ -34,6 +34,9 @@ impl Miner {
             latest.hash.unwrap(),
             all_transactions,
         );
+        block.miner = self.miner_address;
+        block.base_fee_per_gas = latest.next_base_fee();
+        blockchain.prepare_block(&mut block)?;
 
         let attempts = block.mine(difficulty);
 
@@ -49,17 +52,17 @@ impl Miner {
         use crate::transaction::Transaction;
         use ethereum_types::U256;
 
-        let mut coinbase = Transaction {
-            from: Address::zero(),
-            to: Some(self.miner_address),
-            value: U256::from(self.block_reward),
-            data: b"Block reward".to_vec(),
-            gas_limit: 0,
-            gas_price: U256::zero(),
-            nonce: 0,
-            hash: None,
-            tx_type: TransactionType::Transfer
-        };
+        let mut coinbase = Transaction::new_with_gas(
+            Address::zero(),
+            Some(self.miner_address),
+            U256::from(self.block_reward),
+            b"Block reward".to_vec(),
+            0,
+            U256::zero(),
+            // The block number keeps otherwise identical rewards from sharing a hash.
+            blockchain.get_latest_block().number + 1,
+            TransactionType::Transfer,
+        );
 
         coinbase.set_hash();
         coinbase
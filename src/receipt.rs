@@ -0,0 +1,196 @@
+use crate::evm::{ContractExecutionResult, InternalOperation};
+use crate::revert::decode_revert_reason;
+use crate::transaction::{EnvelopeType, Transaction};
+use crate::trie::ordered_trie_root;
+use ethereum_types::{Address, Bloom, BloomInput, H256, U256};
+use rlp::RlpStream;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReceiptLog {
+    pub address: Address,
+    pub topics: Vec<H256>,
+    pub data: Vec<u8>,
+    /// Position of the log within the whole block, not just the transaction.
+    pub log_index: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TransactionReceipt {
+    pub transaction_hash: H256,
+    pub transaction_index: u64,
+    pub block_hash: H256,
+    pub block_number: u64,
+    pub from: Address,
+    pub to: Option<Address>,
+    pub envelope_type: EnvelopeType,
+    pub status: bool,
+    pub gas_used: u64,
+    pub cumulative_gas_used: u64,
+    pub effective_gas_price: U256,
+    /// Total paid by the sender for gas: `gas_used × effective_gas_price`.
+    pub fee: U256,
+    pub contract_address: Option<Address>,
+    /// Decoded revert data of a reverted transaction.
+    pub revert_reason: Option<String>,
+    pub logs: Vec<ReceiptLog>,
+    pub logs_bloom: Bloom,
+    /// Value transfers, self-destructs and creations made by contract code.
+    pub internal_operations: Vec<InternalOperation>,
+}
+
+impl TransactionReceipt {
+    /// Builds the receipt for the transaction at `transaction_index`. `first_log_index` is the
+    /// number of logs emitted by earlier transactions in the same block.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        tx: &Transaction,
+        transaction_index: u64,
+        block_number: u64,
+        result: Option<&ContractExecutionResult>,
+        gas_used: u64,
+        cumulative_gas_used: u64,
+        effective_gas_price: U256,
+        first_log_index: u64,
+    ) -> Self {
+        let logs: Vec<ReceiptLog> = result
+            .map(|result| result.logs.as_slice())
+            .unwrap_or_default()
+            .iter()
+            .zip(first_log_index..)
+            .map(|(log, log_index)| ReceiptLog {
+                address: log.address,
+                topics: log.topics.clone(),
+                data: log.data.clone(),
+                log_index,
+            })
+            .collect();
+
+        TransactionReceipt {
+            transaction_hash: tx.hash.unwrap_or_else(|| tx.calculate_hash()),
+            transaction_index,
+            block_hash: H256::zero(),
+            block_number,
+            from: tx.from,
+            to: tx.to,
+            envelope_type: tx.envelope_type,
+            status: result.map(|result| result.success).unwrap_or(true),
+            gas_used,
+            cumulative_gas_used,
+            effective_gas_price,
+            fee: U256::from(gas_used) * effective_gas_price,
+            contract_address: result.and_then(|result| result.contract_address),
+            revert_reason: result
+                .filter(|result| result.is_revert())
+                .and_then(|result| decode_revert_reason(&result.return_data)),
+            logs_bloom: logs_bloom(logs.iter().map(|log| (&log.address, log.topics.as_slice()))),
+            logs,
+            internal_operations: Vec::new(),
+        }
+    }
+}
+
+impl TransactionReceipt {
+    /// Consensus encoding (EIP-2718 typed for non-legacy transactions).
+    pub fn encode(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(4);
+        stream.append(&(self.status as u8));
+        stream.append(&self.cumulative_gas_used);
+        stream.append(&self.logs_bloom);
+        stream.begin_list(self.logs.len());
+        for log in &self.logs {
+            stream.begin_list(3);
+            stream.append(&log.address);
+            stream.append_list(&log.topics);
+            stream.append(&log.data);
+        }
+
+        match self.envelope_type {
+            EnvelopeType::Legacy => stream.out().to_vec(),
+            envelope_type => [&[envelope_type.type_byte()], stream.out().as_ref()].concat(),
+        }
+    }
+}
+
+/// Trie root over the encoded receipts, keyed by their transaction's index in the block.
+pub fn receipts_root(receipts: &[TransactionReceipt]) -> H256 {
+    ordered_trie_root(receipts.iter().map(|receipt| receipt.encode()))
+}
+
+/// Bloom over every log address and topic, as used for receipts and block headers.
+pub fn logs_bloom<'a>(logs: impl IntoIterator<Item = (&'a Address, &'a [H256])>) -> Bloom {
+    let mut bloom = Bloom::zero();
+    for (address, topics) in logs {
+        bloom.accrue(BloomInput::Raw(address.as_bytes()));
+        for topic in topics {
+            bloom.accrue(BloomInput::Raw(topic.as_bytes()));
+        }
+    }
+    bloom
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evm::{EvmLog, ExecutionOutcome};
+
+    fn execution_result(logs: Vec<EvmLog>) -> ContractExecutionResult {
+        ContractExecutionResult {
+            success: true,
+            gas_used: 30_000,
+            gas_refunded: 0,
+            return_data: vec![],
+            contract_address: None,
+            logs,
+            outcome: ExecutionOutcome::Success,
+            reason: "Return".to_string(),
+            error: None,
+        }
+    }
+
+    #[test]
+    fn test_receipt_log_indices_continue_block_numbering() {
+        let mut tx = Transaction::new_contract_call(Address::from([1u8; 20]), Address::from([2u8; 20]), vec![1], U256::zero(), 0);
+        tx.set_hash();
+
+        let log = EvmLog { address: Address::from([2u8; 20]), topics: vec![H256::from_low_u64_be(7)], data: vec![] };
+        let result = execution_result(vec![log.clone(), log]);
+
+        let receipt = TransactionReceipt::new(&tx, 1, 5, Some(&result), 30_000, 51_000, U256::from(1), 3);
+
+        assert!(receipt.status);
+        assert_eq!(receipt.transaction_hash, tx.hash.unwrap());
+        assert_eq!(receipt.logs.iter().map(|log| log.log_index).collect::<Vec<_>>(), vec![3, 4]);
+        assert_eq!(receipt.cumulative_gas_used, 51_000);
+        assert_eq!(receipt.fee, U256::from(30_000));
+    }
+
+    #[test]
+    fn test_reverted_receipt_carries_decoded_reason() {
+        let mut tx = Transaction::new_contract_call(Address::from([1u8; 20]), Address::from([2u8; 20]), vec![1], U256::zero(), 0);
+        tx.set_hash();
+
+        let mut result = execution_result(vec![]);
+        result.success = false;
+        result.outcome = ExecutionOutcome::Revert;
+        result.reason = "Revert".to_string();
+        // Panic(0x01): a failed assert.
+        result.return_data = hex::decode("4e487b710000000000000000000000000000000000000000000000000000000000000001").unwrap();
+
+        let receipt = TransactionReceipt::new(&tx, 0, 5, Some(&result), 30_000, 30_000, U256::from(1), 0);
+
+        assert!(!receipt.status);
+        assert_eq!(receipt.revert_reason, Some("panic: assertion failed (0x01)".to_string()));
+    }
+
+    #[test]
+    fn test_logs_bloom_contains_address_and_topics() {
+        let address = Address::from([2u8; 20]);
+        let topic = H256::from_low_u64_be(7);
+        let bloom = logs_bloom([(&address, std::slice::from_ref(&topic))]);
+
+        assert!(bloom.contains_input(BloomInput::Raw(address.as_bytes())));
+        assert!(bloom.contains_input(BloomInput::Raw(topic.as_bytes())));
+        assert!(!bloom.contains_input(BloomInput::Raw(Address::from([3u8; 20]).as_bytes())));
+    }
+}
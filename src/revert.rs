@@ -0,0 +1,271 @@
+use ethereum_types::{Address, U256};
+use sha3::{Digest, Keccak256};
+use std::collections::HashMap;
+
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0]; // Error(string)
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71]; // Panic(uint256)
+
+/// Custom errors revert data is decoded against, registered by their Solidity signature
+/// from the ABIs of the contracts in use.
+#[derive(Debug, Clone, Default)]
+pub struct ErrorRegistry {
+    signatures: HashMap<[u8; 4], String>,
+}
+
+impl ErrorRegistry {
+    pub fn new() -> Self {
+        ErrorRegistry::default()
+    }
+
+    /// Registers a signature such as `ERC20InsufficientBalance(address,uint256,uint256)`.
+    pub fn register(&mut self, signature: &str) {
+        let digest = Keccak256::digest(signature.as_bytes());
+        self.signatures.insert([digest[0], digest[1], digest[2], digest[3]], signature.to_string());
+    }
+
+    /// Like `decode_revert_reason`, but names registered custom errors and their arguments.
+    pub fn decode(&self, data: &[u8]) -> Option<String> {
+        let selector: [u8; 4] = data.get(..4)?.try_into().ok()?;
+        self.signatures.get(&selector)
+            .and_then(|signature| decode_custom_error(signature, &data[4..]))
+            .or_else(|| decode_revert_reason(data))
+    }
+}
+
+/// Human-readable reason for revert data: the `Error(string)` message, a described
+/// `Panic(uint256)`, or the selector and raw arguments of a custom error. Returns `None`
+/// when there is nothing to decode.
+pub fn decode_revert_reason(data: &[u8]) -> Option<String> {
+    let selector: [u8; 4] = data.get(..4)?.try_into().ok()?;
+    let payload = &data[4..];
+
+    match selector {
+        ERROR_SELECTOR => decode_string(payload),
+        PANIC_SELECTOR => {
+            let code = U256::from_big_endian(payload.get(..32)?);
+            Some(format!("panic: {} (0x{:02x})", panic_description(code), code))
+        }
+        _ if payload.is_empty() => Some(format!("custom error 0x{}", hex::encode(selector))),
+        _ => Some(format!("custom error 0x{} (0x{})", hex::encode(selector), hex::encode(payload))),
+    }
+}
+
+/// Meaning of the codes Solidity's compiler-inserted checks panic with.
+fn panic_description(code: U256) -> &'static str {
+    if code > U256::from(u8::MAX) {
+        return "unknown panic code";
+    }
+
+    match code.as_u64() {
+        0x00 => "generic compiler panic",
+        0x01 => "assertion failed",
+        0x11 => "arithmetic underflow or overflow",
+        0x12 => "division or modulo by zero",
+        0x21 => "invalid enum value",
+        0x22 => "invalid storage byte array encoding",
+        0x31 => "pop on empty array",
+        0x32 => "array index out of bounds",
+        0x41 => "out of memory",
+        0x51 => "call to zero-initialized function",
+        _ => "unknown panic code",
+    }
+}
+
+fn decode_custom_error(signature: &str, payload: &[u8]) -> Option<String> {
+    let (name, params) = signature.trim_end_matches(')').split_once('(')?;
+    let params = params.split(',')
+        .filter(|param| !param.is_empty())
+        .map(ParamType::parse)
+        .collect::<Option<Vec<_>>>();
+    // Without a decoding for every type, not even the position of each argument is known.
+    let Some(params) = params else {
+        return Some(format!("{}(0x{})", name, hex::encode(payload)));
+    };
+
+    let args = params.iter()
+        .enumerate()
+        .map(|(index, param)| param.decode(payload, index))
+        .collect::<Option<Vec<_>>>()?;
+
+    Some(format!("{}({})", name, args.join(", ")))
+}
+
+/// ABI types custom error arguments can be decoded as. Each takes a single head word; the
+/// contents of `string` and `bytes` live behind the offset stored there.
+enum ParamType {
+    Address,
+    Bool,
+    Uint,
+    Int(usize),
+    FixedBytes(usize),
+    String,
+    Bytes,
+}
+
+impl ParamType {
+    fn parse(param: &str) -> Option<Self> {
+        let bits = |bits: &str| match bits {
+            "" => Some(256),
+            _ => bits.parse().ok().filter(|bits| bits % 8 == 0 && (8..=256).contains(bits)),
+        };
+
+        match param {
+            "address" => Some(ParamType::Address),
+            "bool" => Some(ParamType::Bool),
+            "string" => Some(ParamType::String),
+            "bytes" => Some(ParamType::Bytes),
+            _ => {
+                if let Some(size) = param.strip_prefix("uint") {
+                    bits(size).map(|_| ParamType::Uint)
+                } else if let Some(size) = param.strip_prefix("int") {
+                    bits(size).map(ParamType::Int)
+                } else {
+                    let size = param.strip_prefix("bytes")?.parse().ok()?;
+                    (1..=32).contains(&size).then_some(ParamType::FixedBytes(size))
+                }
+            }
+        }
+    }
+
+    /// Formats the argument in head word `index` of `payload`.
+    fn decode(&self, payload: &[u8], index: usize) -> Option<String> {
+        let word = payload.get(index * 32..(index + 1) * 32)?;
+        let value = U256::from_big_endian(word);
+
+        match self {
+            ParamType::Address => Some(format!("0x{}", hex::encode(Address::from_slice(&word[12..]).as_bytes()))),
+            ParamType::Bool if value.is_zero() => Some("false".to_string()),
+            ParamType::Bool if value == U256::one() => Some("true".to_string()),
+            ParamType::Bool => None,
+            ParamType::Uint => Some(value.to_string()),
+            ParamType::Int(bits) => {
+                let mask = if *bits == 256 { U256::MAX } else { (U256::one() << *bits) - 1 };
+                let value = value & mask;
+                if value.bit(bits - 1) {
+                    Some(format!("-{}", ((!value) & mask) + 1))
+                } else {
+                    Some(value.to_string())
+                }
+            }
+            ParamType::FixedBytes(size) => Some(format!("0x{}", hex::encode(&word[..*size]))),
+            ParamType::String => {
+                let string = String::from_utf8(decode_dynamic(payload, index)?.to_vec()).ok()?;
+                Some(format!("{:?}", string))
+            }
+            ParamType::Bytes => Some(format!("0x{}", hex::encode(decode_dynamic(payload, index)?))),
+        }
+    }
+}
+
+/// Decodes an ABI-encoded dynamic `string` argument.
+fn decode_string(payload: &[u8]) -> Option<String> {
+    String::from_utf8(decode_dynamic(payload, 0)?.to_vec()).ok()
+}
+
+/// Contents of the dynamic `string` or `bytes` argument whose offset is in head word `index`.
+fn decode_dynamic(payload: &[u8], index: usize) -> Option<&[u8]> {
+    let head = index.checked_mul(32)?;
+    let offset = usize::try_from(U256::from_big_endian(payload.get(head..head.checked_add(32)?)?)).ok()?;
+    let length = U256::from_big_endian(payload.get(offset..offset.checked_add(32)?)?);
+    let start = offset + 32;
+    let end = start.checked_add(usize::try_from(length).ok()?)?;
+    payload.get(start..end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn word(value: u64) -> Vec<u8> {
+        let mut word = vec![0u8; 32];
+        U256::from(value).to_big_endian(&mut word);
+        word
+    }
+
+    #[test]
+    fn test_decode_error_string() {
+        // Error("Insufficient balance")
+        let data = hex::decode(
+            "08c379a0\
+             0000000000000000000000000000000000000000000000000000000000000020\
+             0000000000000000000000000000000000000000000000000000000000000014\
+             496e73756666696369656e742062616c616e6365000000000000000000000000",
+        ).unwrap();
+
+        assert_eq!(decode_revert_reason(&data), Some("Insufficient balance".to_string()));
+        assert_eq!(decode_revert_reason(&data[..40]), None);
+        assert_eq!(decode_revert_reason(&[]), None);
+    }
+
+    #[test]
+    fn test_decode_panic() {
+        let data = [PANIC_SELECTOR.to_vec(), word(0x11)].concat();
+        assert_eq!(decode_revert_reason(&data), Some("panic: arithmetic underflow or overflow (0x11)".to_string()));
+
+        let data = [PANIC_SELECTOR.to_vec(), word(0x99)].concat();
+        assert_eq!(decode_revert_reason(&data), Some("panic: unknown panic code (0x99)".to_string()));
+    }
+
+    #[test]
+    fn test_decode_custom_errors() {
+        let mut errors = ErrorRegistry::new();
+        errors.register("ERC20InsufficientBalance(address,uint256,uint256)");
+        errors.register("OwnableUnauthorizedAccount(address)");
+
+        // The payload eth_call returned for an ERC-20 transfer from an empty account.
+        let data = [
+            hex::decode("e450d38c").unwrap(),
+            word(1),
+            word(0),
+            word(1000),
+        ].concat();
+        assert_eq!(
+            errors.decode(&data),
+            Some("ERC20InsufficientBalance(0x0000000000000000000000000000000000000001, 0, 1000)".to_string()),
+        );
+
+        let data = [hex::decode("118cdaa7").unwrap(), word(2)].concat();
+        assert_eq!(
+            errors.decode(&data),
+            Some("OwnableUnauthorizedAccount(0x0000000000000000000000000000000000000002)".to_string()),
+        );
+        // Without the ABI only the selector and raw arguments are known.
+        assert_eq!(
+            decode_revert_reason(&data),
+            Some(format!("custom error 0x118cdaa7 (0x{})", hex::encode(word(2)))),
+        );
+
+        assert_eq!(errors.decode(&[0xde, 0xad, 0xbe, 0xef]), Some("custom error 0xdeadbeef".to_string()));
+        assert_eq!(errors.decode(&[PANIC_SELECTOR.to_vec(), word(1)].concat()), Some("panic: assertion failed (0x01)".to_string()));
+    }
+
+    #[test]
+    fn test_decode_custom_error_argument_types() {
+        let mut errors = ErrorRegistry::new();
+        errors.register("Rejected(string,int8,bool,bytes2,int256)");
+        errors.register("Unsupported(uint256[2])");
+
+        // The string's offset comes first; its contents follow the five head words.
+        let mut int8_min = vec![0u8; 32];
+        int8_min[31] = 0x80;
+        let mut minus_two = vec![0xff; 32];
+        minus_two[31] = 0xfe;
+        let data = [
+            Keccak256::digest(b"Rejected(string,int8,bool,bytes2,int256)")[..4].to_vec(),
+            word(5 * 32),
+            int8_min,
+            word(1),
+            [vec![0xab, 0xcd], vec![0; 30]].concat(),
+            minus_two,
+            word(2),
+            [b"no".to_vec(), vec![0; 30]].concat(),
+        ].concat();
+        assert_eq!(errors.decode(&data), Some(r#"Rejected("no", -128, true, 0xabcd, -2)"#.to_string()));
+
+        let data = [Keccak256::digest(b"Unsupported(uint256[2])")[..4].to_vec(), word(1), word(2)].concat();
+        assert_eq!(
+            errors.decode(&data),
+            Some(format!("Unsupported(0x{}{})", hex::encode(word(1)), hex::encode(word(2)))),
+        );
+    }
+}
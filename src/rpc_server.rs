@@ -2,25 +2,47 @@ use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
 use serde_json::{json, Value};
 use warp::{Filter, Reply};
+use revm::inspectors::NoOpInspector;
+use revm::Inspector;
 use ethereum_types::{Address, U256, H256};
-use crate::blockchain::Blockchain;
+use crate::block::Block;
+use crate::blockchain::{Blockchain, SimulatedBlock, SimulatedBlockCalls};
+use crate::account::WorldState;
+use crate::assets::{AssetChanges, Delta};
+use crate::evm::{
+    AccountOverride, BlockOverrides, CallFrame, CallOverrides, CallRequest, CallTracer, ChainDatabase, ContractExecutionResult, StateDiff, StructLog, StructLogger,
+    StructLoggerConfig, Trace,
+};
+use crate::hardfork::{ForkCondition, Hardfork};
+use crate::revert::ErrorRegistry;
 use crate::miner::Miner;
-use crate::transaction::{Transaction, TransactionType};
+use crate::receipt::TransactionReceipt;
+use crate::transaction::{address_from_secret_key, EnvelopeType, Transaction, TransactionType};
+
+/// keccak256(rlp([])): every block reports an empty uncle list.
+const EMPTY_UNCLES_HASH: &str = "0x1dcc4de8dec75d7aab85b567b6ccd41ad312451b948a7413f0a142fd40d49347";
 
 pub struct RpcServer {
     blockchain: Arc<Mutex<Blockchain>>,
     miner: Arc<Miner>,
     pending_transactions: Arc<Mutex<Vec<Transaction>>>,
     auto_mining: Arc<Mutex<bool>>,
+    unlocked_accounts: HashMap<Address, [u8; 32]>,
 }
 
 impl RpcServer {
-    pub fn new(blockchain: Blockchain, miner: Miner) -> Self {
+    /// `unlocked_keys` are the node-held keys `eth_sendTransaction` may sign with.
+    pub fn new(blockchain: Blockchain, miner: Miner, unlocked_keys: &[[u8; 32]]) -> Self {
+        let unlocked_accounts = unlocked_keys.iter()
+            .map(|key| (address_from_secret_key(key).expect("Invalid unlocked account key"), *key))
+            .collect();
+
         RpcServer {
             blockchain: Arc::new(Mutex::new(blockchain)),
             miner: Arc::new(miner),
             pending_transactions: Arc::new(Mutex::new(Vec::new())),
             auto_mining: Arc::new(Mutex::new(true)), // Auto-mine by default
+            unlocked_accounts,
         }
     }
 
@@ -67,6 +89,27 @@ async fn handle_rpc_request(
     Ok(warp::reply::json(&response))
 }
 
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl Into<String>) -> Self {
+        RpcError { code, message: message.into(), data: None }
+    }
+
+    fn to_json(&self) -> Value {
+        match &self.data {
+            Some(data) => json!({ "code": self.code, "message": self.message, "data": data }),
+            None => json!({ "code": self.code, "message": self.message }),
+        }
+    }
+}
+
+type RpcResult = Result<Value, RpcError>;
+
 async fn process_rpc_request(request: &Value, server: &Arc<RpcServer>) -> Value {
     let method = request["method"].as_str().unwrap_or("");
     let params = &request["params"];
@@ -75,39 +118,84 @@ async fn process_rpc_request(request: &Value, server: &Arc<RpcServer>) -> Value
     println!("RPC Request: {} {:?}", method, params);
 
     let result = match method {
-        "eth_chainId" => json!("0x539"), // 1337 in hex
-        "net_version" => json!("1337"),
-        "eth_blockNumber" => handle_block_number(server),
-        "eth_getBalance" => handle_get_balance(params, server),
-        "eth_getTransactionCount" => handle_get_transaction_count(params, server),
+        "eth_chainId" => Ok(handle_chain_id(server)),
+        "net_version" => Ok(json!(server.blockchain.lock().unwrap().chain_id.to_string())),
+        "eth_blockNumber" => Ok(handle_block_number(server)),
+        "eth_getBalance" => Ok(handle_get_balance(params, server)),
+        "eth_getTransactionCount" => Ok(handle_get_transaction_count(params, server)),
         "eth_sendTransaction" => handle_send_transaction(params, server).await,
         "eth_sendRawTransaction" => handle_send_raw_transaction(params, server).await,
         "eth_call" => handle_eth_call(params, server).await,
-        "eth_getCode" => handle_get_code(params, server),
+        "eth_getCode" => Ok(handle_get_code(params, server)),
         "eth_getBlockByNumber" => handle_get_block_by_number(params, server),
-        "eth_getTransactionReceipt" => handle_get_transaction_receipt(params, server),
-        "eth_gasPrice" => json!("0x4a817c800"), // 20 gwei
-        "eth_estimateGas" => json!("0x5208"), // 21000 gas
-        "web3_clientVersion" => json!("RustBlockchain/1.0.0"),
-        "eth_accounts" => handle_eth_accounts(),
+        "eth_getBlockByHash" => Ok(handle_get_block_by_hash(params, server)),
+        "eth_getTransactionByHash" => Ok(handle_get_transaction_by_hash(params, server)),
+        "eth_getTransactionByBlockNumberAndIndex" => handle_get_transaction_by_block_number_and_index(params, server),
+        "eth_getTransactionByBlockHashAndIndex" => Ok(handle_get_transaction_by_block_hash_and_index(params, server)),
+        "eth_getTransactionReceipt" => Ok(handle_get_transaction_receipt(params, server)),
+        "eth_gasPrice" => Ok(handle_gas_price(server)),
+        "eth_maxPriorityFeePerGas" => Ok(handle_max_priority_fee_per_gas(server)),
+        "eth_feeHistory" => handle_fee_history(params, server),
+        "eth_estimateGas" => handle_estimate_gas(params, server),
+        "eth_simulateV1" => handle_simulate(params, server),
+        "debug_chainConfig" => handle_chain_config(params, server),
+        "debug_traceTransaction" => handle_trace_transaction(params, server),
+        "debug_traceCall" => handle_trace_call(params, server),
+        "debug_assetChanges" => handle_asset_changes(params, server),
+        "ots_getInternalOperations" => handle_get_internal_operations(params, server),
+        "web3_clientVersion" => Ok(json!("RustBlockchain/1.0.0")),
+        "eth_accounts" => Ok(handle_eth_accounts(server)),
         _ => {
             println!("Unknown method: {}", method);
-            return json!({
-                "jsonrpc": "2.0",
-                "id": id,
-                "error": {
-                    "code": -32601,
-                    "message": format!("Method {} not found", method)
-                }
-            });
+            Err(RpcError::new(-32601, format!("Method {} not found", method)))
         }
     };
 
-    json!({
-        "jsonrpc": "2.0",
-        "id": id,
-        "result": result
-    })
+    match result {
+        Ok(result) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "result": result
+        }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": error.to_json()
+        }),
+    }
+}
+
+fn handle_chain_id(server: &Arc<RpcServer>) -> Value {
+    let blockchain = server.blockchain.lock().unwrap();
+    json!(format!("0x{:x}", blockchain.chain_id))
+}
+
+/// The fork schedule and the fork active at the requested block (latest by default).
+fn handle_chain_config(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
+    let blockchain = server.blockchain.lock().unwrap();
+    let block_number = parse_block_number(params[0].as_str().unwrap_or("latest"), &blockchain)?;
+    let block = blockchain.get_block_by_number(block_number)
+        .ok_or_else(|| RpcError::new(-32000, format!("Unknown block {}", block_number)))?;
+
+    let schedule = &blockchain.fork_schedule;
+    Ok(json!({
+        "chainId": format!("0x{:x}", blockchain.chain_id),
+        "blockNumber": format!("0x{:x}", block.number),
+        "hardfork": blockchain.hardfork_at(block).name(),
+        "spec": format!("{:?}", blockchain.spec_at(block)),
+        "nextHardfork": schedule.next_fork(block.number, block.timestamp)
+            .map(|(fork, condition)| fork_to_json(fork, condition)),
+        "forks": schedule.forks().iter()
+            .map(|(fork, condition)| fork_to_json(*fork, *condition))
+            .collect::<Vec<_>>()
+    }))
+}
+
+fn fork_to_json(fork: Hardfork, condition: ForkCondition) -> Value {
+    match condition {
+        ForkCondition::Block(number) => json!({ "name": fork.name(), "block": format!("0x{:x}", number) }),
+        ForkCondition::Timestamp(time) => json!({ "name": fork.name(), "timestamp": format!("0x{:x}", time) }),
+    }
 }
 
 fn handle_block_number(server: &Arc<RpcServer>) -> Value {
@@ -136,38 +224,74 @@ fn handle_get_transaction_count(params: &Value, server: &Arc<RpcServer>) -> Valu
     json!(format!("0x{:x}", nonce))
 }
 
-async fn handle_send_transaction(params: &Value, server: &Arc<RpcServer>) -> Value {
+async fn handle_send_transaction(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
     let tx_params = &params[0];
 
     let from = parse_address(tx_params["from"].as_str().unwrap_or(""));
+    let secret_key = server.unlocked_accounts.get(&from)
+        .ok_or_else(|| RpcError::new(-32000, format!("unknown account 0x{}", hex::encode(from.as_bytes()))))?;
+
     let to = tx_params["to"].as_str().map(parse_address);
     let value = parse_u256(tx_params["value"].as_str().unwrap_or("0x0"));
     let data = parse_hex_data(tx_params["data"].as_str().unwrap_or("0x"));
     let gas_limit = parse_u64(tx_params["gas"].as_str().unwrap_or("0x5208"));
     let gas_price = parse_u256(tx_params["gasPrice"].as_str().unwrap_or("0x4a817c800"));
 
-    let nonce = {
+    let (nonce, chain_id, suggested_priority_fee) = {
         let blockchain = server.blockchain.lock().unwrap();
-        blockchain.state.get_nonce(&from)
+        // Transactions still waiting in the pool come first.
+        let queued = server.pending_transactions.lock().unwrap().iter().filter(|tx| tx.from == from).count();
+        (blockchain.state.get_nonce(&from) + queued as u64, blockchain.chain_id, blockchain.suggest_priority_fee())
     };
 
-    let tx_type = if to.is_none() {
-        TransactionType::ContractDeployment
-    } else if !data.is_empty() {
-        TransactionType::ContractCall
-    } else {
-        TransactionType::Transfer
-    };
+    let tx_type = TransactionType::classify(to, &data, false);
 
     let mut tx = Transaction::new_with_gas(
         from, to, value, data, gas_limit, gas_price, nonce, tx_type
     );
-    tx.set_hash();
+    if let Some(max_fee) = tx_params["maxFeePerGas"].as_str().map(parse_u256) {
+        let max_priority_fee = tx_params["maxPriorityFeePerGas"].as_str().map(parse_u256)
+            .unwrap_or(suggested_priority_fee)
+            .min(max_fee);
+        tx.set_dynamic_fee(max_fee, max_priority_fee);
+    }
+    tx.sign(secret_key, chain_id)
+        .map_err(|e| RpcError::new(-32000, e))?;
 
+    let tx_hash = submit_transaction(tx, server).await?;
+    Ok(json!(format!("0x{:x}", tx_hash)))
+}
+
+async fn handle_send_raw_transaction(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
+    let raw_tx = params[0].as_str().unwrap_or("");
+    println!("📝 Raw transaction received: {}", raw_tx);
+
+    let tx = Transaction::decode(&parse_hex_data(raw_tx))
+        .map_err(|e| RpcError::new(-32000, e))?;
+
+    let chain_id = server.blockchain.lock().unwrap().chain_id;
+    match tx.chain_id {
+        Some(id) if id == chain_id => {}
+        Some(id) => return Err(RpcError::new(-32000, format!("invalid chain id: expected {}, got {}", chain_id, id))),
+        None => return Err(RpcError::new(-32000, "only replay-protected (EIP-155) transactions allowed over RPC")),
+    }
+
+    println!("Decoded {:?} transaction from 0x{}", tx.envelope_type, hex::encode(tx.from.as_bytes()));
+
+    let tx_hash = submit_transaction(tx, server).await?;
+    Ok(json!(format!("0x{:x}", tx_hash)))
+}
+
+/// Adds `tx` to the pending pool if it could be mined in the next block after the
+/// transactions already there.
+async fn submit_transaction(tx: Transaction, server: &Arc<RpcServer>) -> Result<H256, RpcError> {
     let tx_hash = tx.hash.unwrap();
 
     {
+        let blockchain = server.blockchain.lock().unwrap();
         let mut pending = server.pending_transactions.lock().unwrap();
+        blockchain.validate_pending_transaction(&tx, &pending)
+            .map_err(|e| RpcError::new(-32000, e))?;
         pending.push(tx);
     }
 
@@ -175,26 +299,447 @@ async fn handle_send_transaction(params: &Value, server: &Arc<RpcServer>) -> Val
         mine_pending_transactions(server).await;
     }
 
-    json!(format!("0x{:x}", tx_hash))
+    Ok(tx_hash)
 }
 
-async fn handle_send_raw_transaction(params: &Value, server: &Arc<RpcServer>) -> Value {
-    let raw_tx = params[0].as_str().unwrap_or("");
-    println!("📝 Raw transaction received: {}", raw_tx);
-    json!("0x1234567890abcdef1234567890abcdef1234567890abcdef1234567890abcdef")
+async fn handle_eth_call(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
+    let request = parse_call_request(&params[0]);
+    let overrides = parse_call_overrides(&params[2], &params[3])?;
+
+    let blockchain = server.blockchain.lock().unwrap();
+    let block_number = parse_block_number(params[1].as_str().unwrap_or("latest"), &blockchain)?;
+    let result = blockchain.call_with_overrides(&request, block_number, &overrides)
+        .map_err(|e| RpcError::new(-32000, e))?;
+
+    if result.success {
+        Ok(json!(format!("0x{}", hex::encode(&result.return_data))))
+    } else {
+        Err(execution_error(&result, &blockchain.custom_errors))
+    }
+}
+
+/// What a debug_trace* request executes.
+enum TraceTarget {
+    Transaction(H256),
+    Call(CallRequest, u64),
+}
+
+/// The tracers selectable through the `tracer` option.
+enum Tracer {
+    StructLogger(StructLoggerConfig),
+    Call,
+    Prestate { diff_mode: bool },
+}
+
+fn handle_trace_transaction(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
+    let hash = parse_h256(params[0].as_str().unwrap_or(""));
+    let tracer = parse_tracer(&params[1])?;
+
+    let blockchain = server.blockchain.lock().unwrap();
+    run_tracer(&blockchain, &TraceTarget::Transaction(hash), tracer)
+}
+
+fn handle_trace_call(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
+    let request = parse_call_request(&params[0]);
+    let tracer = parse_tracer(&params[2])?;
+
+    let blockchain = server.blockchain.lock().unwrap();
+    let block_number = parse_block_number(params[1].as_str().unwrap_or("latest"), &blockchain)?;
+    run_tracer(&blockchain, &TraceTarget::Call(request, block_number), tracer)
+}
+
+/// Asset changes of a mined transaction, given its hash, or of a call object simulated at the
+/// block in params[1].
+fn handle_asset_changes(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
+    let blockchain = server.blockchain.lock().unwrap();
+    if let Some(hash) = params[0].as_str() {
+        return mined_asset_changes(&blockchain, &parse_h256(hash));
+    }
+
+    let request = parse_call_request(&params[0]);
+    let block_number = parse_block_number(params[1].as_str().unwrap_or("latest"), &blockchain)?;
+    let (result, changes) = blockchain.simulate_asset_changes(&request, block_number)
+        .map_err(|e| RpcError::new(-32000, e))?;
+
+    let mut value = asset_changes_to_json(&changes);
+    value["status"] = json!(if result.success { "0x1" } else { "0x0" });
+    value["gasUsed"] = json!(format!("0x{:x}", result.gas_used));
+    if let Some(error) = &result.error {
+        value["error"] = json!(error);
+    }
+    Ok(value)
+}
+
+/// Token deltas come from the receipt. Native ones need a replay; once the state is pruned
+/// `native` is null and `nativeError` says why.
+fn mined_asset_changes(blockchain: &Blockchain, hash: &H256) -> RpcResult {
+    let (receipt, mut changes) = blockchain.transaction_token_changes(hash)
+        .map_err(|e| RpcError::new(-32000, e))?;
+    let native_error = match blockchain.transaction_native_changes(hash) {
+        Ok(native) => {
+            changes.native = native;
+            None
+        }
+        Err(e) => Some(e),
+    };
+
+    let mut value = asset_changes_to_json(&changes);
+    value["status"] = json!(if receipt.status { "0x1" } else { "0x0" });
+    value["gasUsed"] = json!(format!("0x{:x}", receipt.gas_used));
+    if let Some(reason) = &receipt.revert_reason {
+        value["error"] = json!(reason);
+    }
+    if let Some(error) = native_error {
+        value["native"] = Value::Null;
+        value["nativeError"] = json!(error);
+    }
+    Ok(value)
+}
+
+fn parse_tracer(options: &Value) -> Result<Tracer, RpcError> {
+    match options["tracer"].as_str() {
+        None => Ok(Tracer::StructLogger(StructLoggerConfig {
+            enable_memory: options["enableMemory"].as_bool().unwrap_or(false),
+            disable_stack: options["disableStack"].as_bool().unwrap_or(false),
+            disable_storage: options["disableStorage"].as_bool().unwrap_or(false),
+            enable_return_data: options["enableReturnData"].as_bool().unwrap_or(false),
+        })),
+        Some("callTracer") => Ok(Tracer::Call),
+        Some("prestateTracer") => Ok(Tracer::Prestate {
+            diff_mode: options["tracerConfig"]["diffMode"].as_bool().unwrap_or(false),
+        }),
+        Some(tracer) => Err(RpcError::new(-32602, format!("tracer {} is not supported", tracer))),
+    }
+}
+
+fn run_tracer(blockchain: &Blockchain, target: &TraceTarget, tracer: Tracer) -> RpcResult {
+    match tracer {
+        Tracer::StructLogger(config) => {
+            let trace = trace(blockchain, target, StructLogger::new(config))?;
+            Ok(struct_trace_to_json(&trace.result, &trace.inspector.into_logs()))
+        }
+        Tracer::Call => {
+            let trace = trace(blockchain, target, CallTracer::new())?;
+            Ok(trace.inspector.into_call_frame(trace.result.gas_used)
+                .map_or(Value::Null, |frame| call_frame_to_json(&frame)))
+        }
+        Tracer::Prestate { diff_mode } => {
+            let trace = trace(blockchain, target, NoOpInspector)?;
+            Ok(prestate_to_json(&trace.state_diff, diff_mode))
+        }
+    }
+}
+
+fn trace<I>(blockchain: &Blockchain, target: &TraceTarget, inspector: I) -> Result<Trace<I>, RpcError>
+where
+    I: for<'s> Inspector<ChainDatabase<'s, &'s WorldState>>,
+{
+    match target {
+        TraceTarget::Transaction(hash) => blockchain.trace_transaction(hash, inspector),
+        TraceTarget::Call(request, block_number) => blockchain.trace_call(request, *block_number, inspector),
+    }
+    .map_err(|e| RpcError::new(-32000, e))
+}
+
+fn struct_trace_to_json(result: &ContractExecutionResult, logs: &[StructLog]) -> Value {
+    json!({
+        "gas": result.gas_used,
+        "failed": !result.success,
+        "returnValue": format!("0x{}", hex::encode(&result.return_data)),
+        "structLogs": logs.iter().map(struct_log_to_json).collect::<Vec<_>>()
+    })
 }
 
-async fn handle_eth_call(params: &Value, server: &Arc<RpcServer>) -> Value {
-    let call_params = &params[0];
-    let to_str = call_params["to"].as_str().unwrap_or("");
-    let data_str = call_params["data"].as_str().unwrap_or("0x");
+fn struct_log_to_json(log: &StructLog) -> Value {
+    let mut entry = json!({
+        "pc": log.pc,
+        "op": log.op,
+        "gas": log.gas,
+        "gasCost": log.gas_cost,
+        "depth": log.depth,
+        "refund": log.refund
+    });
+    if let Some(stack) = &log.stack {
+        entry["stack"] = json!(stack.iter().map(|value| format!("0x{:x}", value)).collect::<Vec<_>>());
+    }
+    if let Some(memory) = &log.memory {
+        entry["memory"] = json!(memory.iter().map(|word| hex::encode(word.as_bytes())).collect::<Vec<_>>());
+    }
+    if let Some(return_data) = &log.return_data {
+        entry["returnData"] = json!(format!("0x{}", hex::encode(return_data)));
+    }
+    if let Some(storage) = &log.storage {
+        entry["storage"] = storage.iter()
+            .map(|(key, value)| (hex::encode(key.as_bytes()), json!(hex::encode(value.as_bytes()))))
+            .collect::<serde_json::Map<_, _>>()
+            .into();
+    }
+    if let Some(error) = &log.error {
+        entry["error"] = json!(error);
+    }
+    entry
+}
 
-    let to = parse_address(to_str);
-    let data = parse_hex_data(data_str);
+fn asset_changes_to_json(changes: &AssetChanges) -> Value {
+    let address = |address: &Address| format!("0x{}", hex::encode(address.as_bytes()));
+    let ids = |ids: &[U256]| ids.iter().map(|id| format!("0x{:x}", id)).collect::<Vec<_>>();
 
-    println!("Contract call to: 0x{}, data: {}", hex::encode(to.as_bytes()), data_str);
+    json!({
+        "native": changes.native.iter().map(|(holder, delta)| json!({
+            "address": address(holder),
+            "delta": delta_to_json(delta)
+        })).collect::<Vec<_>>(),
+        "erc20": changes.erc20.iter().map(|((token, holder), delta)| json!({
+            "token": address(token),
+            "address": address(holder),
+            "delta": delta_to_json(delta)
+        })).collect::<Vec<_>>(),
+        "erc721": changes.erc721.iter().map(|((token, holder), delta)| json!({
+            "token": address(token),
+            "address": address(holder),
+            "received": ids(&delta.received),
+            "sent": ids(&delta.sent)
+        })).collect::<Vec<_>>()
+    })
+}
 
-    json!("0x")
+/// A signed hex quantity: losses are prefixed with `-`.
+fn delta_to_json(delta: &Delta) -> String {
+    if delta.lost.is_zero() {
+        format!("0x{:x}", delta.gained)
+    } else {
+        format!("-0x{:x}", delta.lost)
+    }
+}
+
+fn call_frame_to_json(frame: &CallFrame) -> Value {
+    let mut entry = json!({
+        "type": frame.call_type,
+        "from": format!("{:?}", frame.from),
+        "gas": format!("0x{:x}", frame.gas),
+        "gasUsed": format!("0x{:x}", frame.gas_used),
+        "input": format!("0x{}", hex::encode(&frame.input))
+    });
+    if let Some(to) = frame.to {
+        entry["to"] = json!(format!("{:?}", to));
+    }
+    if let Some(value) = frame.value {
+        entry["value"] = json!(format!("0x{:x}", value));
+    }
+    if !frame.output.is_empty() {
+        entry["output"] = json!(format!("0x{}", hex::encode(&frame.output)));
+    }
+    if let Some(error) = &frame.error {
+        entry["error"] = json!(error);
+    }
+    if let Some(reason) = &frame.revert_reason {
+        entry["revertReason"] = json!(reason);
+    }
+    if !frame.calls.is_empty() {
+        entry["calls"] = json!(frame.calls.iter().map(call_frame_to_json).collect::<Vec<_>>());
+    }
+    entry
+}
+
+/// geth's `prestateTracer` output. Without diff mode it is the pre-state of every touched
+/// account; in diff mode, `pre` and `post` cover only modified accounts, `post` lists only
+/// the fields that changed, and both list only the slots that changed.
+fn prestate_to_json(state_diff: &StateDiff, diff_mode: bool) -> Value {
+    let slots_to_json = |slots: Vec<(&H256, &H256)>| slots.into_iter()
+        .map(|(key, value)| (format!("{:?}", key), json!(format!("{:?}", value))))
+        .collect::<serde_json::Map<_, _>>();
+
+    let mut pre = serde_json::Map::new();
+    let mut post = serde_json::Map::new();
+    for (address, diff) in &state_diff.accounts {
+        if diff_mode && !diff.is_modified() {
+            continue;
+        }
+
+        let mut account = json!({ "balance": format!("0x{:x}", diff.pre.balance) });
+        if diff.pre.nonce > 0 {
+            account["nonce"] = json!(diff.pre.nonce);
+        }
+        if !diff.pre.code.is_empty() {
+            account["code"] = json!(format!("0x{}", hex::encode(&diff.pre.code)));
+        }
+        let pre_slots: Vec<_> = if diff_mode {
+            diff.changed_slots().map(|key| (key, &diff.pre.storage[key])).filter(|(_, value)| !value.is_zero()).collect()
+        } else {
+            diff.pre.storage.iter().collect()
+        };
+        if !pre_slots.is_empty() {
+            account["storage"] = slots_to_json(pre_slots).into();
+        }
+        // Accounts the execution created had no pre-state to report.
+        if !diff_mode || !diff.pre.is_empty() {
+            pre.insert(format!("{:?}", address), account);
+        }
+
+        if diff_mode {
+            let mut account = json!({});
+            if diff.post.balance != diff.pre.balance {
+                account["balance"] = json!(format!("0x{:x}", diff.post.balance));
+            }
+            if diff.post.nonce != diff.pre.nonce {
+                account["nonce"] = json!(diff.post.nonce);
+            }
+            if diff.post.code != diff.pre.code {
+                account["code"] = json!(format!("0x{}", hex::encode(&diff.post.code)));
+            }
+            let post_slots: Vec<_> = diff.changed_slots().map(|key| (key, &diff.post.storage[key])).collect();
+            if !post_slots.is_empty() {
+                account["storage"] = slots_to_json(post_slots).into();
+            }
+            post.insert(format!("{:?}", address), account);
+        }
+    }
+
+    if diff_mode {
+        json!({ "pre": pre, "post": post })
+    } else {
+        Value::Object(pre)
+    }
+}
+
+fn handle_gas_price(server: &Arc<RpcServer>) -> Value {
+    let blockchain = server.blockchain.lock().unwrap();
+    let gas_price = blockchain.get_latest_block().next_base_fee() + blockchain.suggest_priority_fee();
+    json!(format!("0x{:x}", gas_price))
+}
+
+fn handle_max_priority_fee_per_gas(server: &Arc<RpcServer>) -> Value {
+    let blockchain = server.blockchain.lock().unwrap();
+    json!(format!("0x{:x}", blockchain.suggest_priority_fee()))
+}
+
+fn handle_fee_history(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
+    // Block count may be sent as a number or a hex quantity.
+    let block_count = params[0].as_u64()
+        .unwrap_or_else(|| parse_u64(params[0].as_str().unwrap_or("0x1")));
+    let percentiles: Vec<f64> = params[2].as_array()
+        .map(|values| values.iter().filter_map(Value::as_f64).collect())
+        .unwrap_or_default();
+
+    let blockchain = server.blockchain.lock().unwrap();
+    let newest_block = parse_block_number(params[1].as_str().unwrap_or("latest"), &blockchain)?;
+    let history = blockchain.fee_history(block_count, newest_block, &percentiles);
+
+    let mut result = json!({
+        "oldestBlock": format!("0x{:x}", history.oldest_block),
+        "baseFeePerGas": history.base_fee_per_gas.iter().map(|fee| format!("0x{:x}", fee)).collect::<Vec<_>>(),
+        "gasUsedRatio": history.gas_used_ratio
+    });
+    if !percentiles.is_empty() {
+        result["reward"] = json!(history.reward.iter()
+            .map(|rewards| rewards.iter().map(|reward| format!("0x{:x}", reward)).collect::<Vec<_>>())
+            .collect::<Vec<_>>());
+    }
+    Ok(result)
+}
+
+fn handle_estimate_gas(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
+    let request = parse_call_request(&params[0]);
+    let overrides = parse_call_overrides(&params[2], &params[3])?;
+
+    let blockchain = server.blockchain.lock().unwrap();
+    let block_number = parse_block_number(params[1].as_str().unwrap_or("latest"), &blockchain)?;
+    let (gas, result) = blockchain.estimate_gas_with_overrides(&request, block_number, &overrides)
+        .map_err(|e| RpcError::new(-32000, e))?;
+
+    if result.success {
+        Ok(json!(format!("0x{:x}", gas)))
+    } else if result.is_out_of_gas() {
+        Err(RpcError::new(-32000, format!("gas required exceeds allowance ({})", gas)))
+    } else {
+        Err(execution_error(&result, &blockchain.custom_errors))
+    }
+}
+
+fn handle_simulate(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
+    let options = &params[0];
+    let blocks = options["blockStateCalls"].as_array()
+        .ok_or_else(|| RpcError::new(-32602, "blockStateCalls is required"))?
+        .iter()
+        .map(|block| Ok(SimulatedBlockCalls {
+            overrides: parse_call_overrides(&block["stateOverrides"], &block["blockOverrides"])?,
+            calls: block["calls"].as_array().into_iter().flatten().map(parse_call_request).collect(),
+        }))
+        .collect::<Result<Vec<_>, RpcError>>()?;
+    let validation = options["validation"].as_bool().unwrap_or(false);
+
+    let blockchain = server.blockchain.lock().unwrap();
+    let block_number = parse_block_number(params[1].as_str().unwrap_or("latest"), &blockchain)?;
+    let simulated = blockchain.simulate(block_number, &blocks, validation)
+        .map_err(|e| RpcError::new(-32000, e))?;
+
+    Ok(json!(simulated.iter()
+        .map(|block| simulated_block_to_json(block, &blockchain.custom_errors))
+        .collect::<Vec<_>>()))
+}
+
+fn simulated_block_to_json(simulated: &SimulatedBlock, custom_errors: &ErrorRegistry) -> Value {
+    let block = &simulated.block;
+    let block_hash = block.hash.unwrap_or_default();
+    let mut log_index = 0u64;
+
+    let calls = simulated.results.iter().zip(&block.transactions).enumerate().map(|(index, (result, tx))| {
+        let logs = result.logs.iter().map(|log| {
+            let entry = json!({
+                "address": format!("0x{}", hex::encode(log.address.as_bytes())),
+                "topics": log.topics.iter().map(|topic| format!("0x{:x}", topic)).collect::<Vec<_>>(),
+                "data": format!("0x{}", hex::encode(&log.data)),
+                "blockHash": format!("0x{:x}", block_hash),
+                "blockNumber": format!("0x{:x}", block.number),
+                "transactionHash": format!("0x{:x}", tx.hash.unwrap_or_default()),
+                "transactionIndex": format!("0x{:x}", index),
+                "logIndex": format!("0x{:x}", log_index),
+                "removed": false
+            });
+            log_index += 1;
+            entry
+        }).collect::<Vec<_>>();
+
+        let mut call = json!({
+            "status": if result.success { "0x1" } else { "0x0" },
+            "returnData": format!("0x{}", hex::encode(&result.return_data)),
+            "gasUsed": format!("0x{:x}", result.gas_used),
+            "logs": logs
+        });
+        if !result.success {
+            // geth reports halts with its VM error code rather than -32000.
+            let mut error = execution_error(result, custom_errors);
+            if error.code != 3 {
+                error.code = -32015;
+            }
+            call["error"] = error.to_json();
+        }
+        call
+    }).collect::<Vec<_>>();
+
+    let mut value = block_to_json(block, false);
+    value["calls"] = json!(calls);
+    value
+}
+
+/// Error for a failed call. Reverts use code 3 with the raw revert data, as other clients
+/// report them; halts carry no data.
+fn execution_error(result: &ContractExecutionResult, custom_errors: &ErrorRegistry) -> RpcError {
+    if !result.is_revert() {
+        return RpcError::new(-32000, result.error.clone().unwrap_or_else(|| result.reason.clone()));
+    }
+
+    let return_data = &result.return_data;
+    let message = match custom_errors.decode(return_data) {
+        Some(reason) => format!("execution reverted: {}", reason),
+        None => "execution reverted".to_string(),
+    };
+
+    RpcError {
+        code: 3,
+        message,
+        data: Some(json!(format!("0x{}", hex::encode(return_data)))),
+    }
 }
 
 fn handle_get_code(params: &Value, server: &Arc<RpcServer>) -> Value {
@@ -211,30 +756,33 @@ fn handle_get_code(params: &Value, server: &Arc<RpcServer>) -> Value {
     }
 }
 
-fn handle_get_block_by_number(params: &Value, server: &Arc<RpcServer>) -> Value {
-    let block_number_str = params[0].as_str().unwrap_or("latest");
+fn handle_get_block_by_number(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
     let include_txs = params[1].as_bool().unwrap_or(false);
 
     let blockchain = server.blockchain.lock().unwrap();
-    let block = if block_number_str == "latest" {
-        blockchain.get_latest_block().clone()
-    } else {
-        blockchain.get_latest_block().clone()
-    };
+    let number = parse_block_number(params[0].as_str().unwrap_or("latest"), &blockchain)?;
+    Ok(match blockchain.get_block_by_number(number) {
+        Some(block) => block_to_json(block, include_txs),
+        None => json!(null),
+    })
+}
 
+fn handle_get_block_by_hash(params: &Value, server: &Arc<RpcServer>) -> Value {
+    let hash = parse_h256(params[0].as_str().unwrap_or(""));
+    let include_txs = params[1].as_bool().unwrap_or(false);
+
+    let blockchain = server.blockchain.lock().unwrap();
+    match blockchain.get_block_by_hash(hash) {
+        Some(block) => block_to_json(block, include_txs),
+        None => json!(null),
+    }
+}
+
+fn block_to_json(block: &Block, include_txs: bool) -> Value {
     let transactions = if include_txs {
-        block.transactions.iter().map(|tx| {
-            json!({
-                "hash": format!("0x{:x}", tx.hash.unwrap_or(H256::zero())),
-                "from": format!("0x{}", hex::encode(tx.from.as_bytes())),
-                "to": tx.to.map(|addr| format!("0x{}", hex::encode(addr.as_bytes()))),
-                "value": format!("0x{:x}", tx.value),
-                "gas": format!("0x{:x}", tx.gas_limit),
-                "gasPrice": format!("0x{:x}", tx.gas_price),
-                "nonce": format!("0x{:x}", tx.nonce),
-                "input": format!("0x{}", hex::encode(&tx.data))
-            })
-        }).collect::<Vec<_>>()
+        block.transactions.iter().enumerate()
+            .map(|(index, tx)| transaction_to_json(tx, Some((block, index))))
+            .collect::<Vec<_>>()
     } else {
         block.transactions.iter().map(|tx|
             json!(format!("0x{:x}", tx.hash.unwrap_or(H256::zero())))
@@ -248,46 +796,251 @@ fn handle_get_block_by_number(params: &Value, server: &Arc<RpcServer>) -> Value
         "timestamp": format!("0x{:x}", block.timestamp),
         "gasLimit": format!("0x{:x}", block.gas_limit),
         "gasUsed": format!("0x{:x}", block.gas_used),
+        "stateRoot": format!("0x{:x}", block.state_root),
+        "transactionsRoot": format!("0x{:x}", block.transactions_root),
+        "receiptsRoot": format!("0x{:x}", block.receipts_root),
+        "logsBloom": format!("0x{}", hex::encode(block.logs_bloom.as_bytes())),
+        "miner": format!("0x{}", hex::encode(block.miner.as_bytes())),
+        "difficulty": format!("0x{:x}", block.difficulty),
+        "totalDifficulty": "0x0",
+        "size": format!("0x{:x}", block.size()),
+        "baseFeePerGas": format!("0x{:x}", block.base_fee_per_gas),
+        "extraData": "0x",
+        "mixHash": format!("0x{:x}", block.prev_randao()),
+        "sha3Uncles": EMPTY_UNCLES_HASH,
+        "uncles": [],
         "transactions": transactions,
         "nonce": format!("0x{:x}", block.nonce)
     })
 }
 
+fn handle_get_transaction_by_hash(params: &Value, server: &Arc<RpcServer>) -> Value {
+    let hash = parse_h256(params[0].as_str().unwrap_or(""));
+
+    let blockchain = server.blockchain.lock().unwrap();
+    match blockchain.get_transaction_by_hash(&hash) {
+        Some((tx, block, index)) => transaction_to_json(tx, Some((block, index))),
+        None => json!(null),
+    }
+}
+
+fn handle_get_transaction_by_block_number_and_index(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
+    let blockchain = server.blockchain.lock().unwrap();
+    let number = parse_block_number(params[0].as_str().unwrap_or("latest"), &blockchain)?;
+    Ok(transaction_in_block_to_json(blockchain.get_block_by_number(number), params[1].as_str().unwrap_or("")))
+}
+
+fn handle_get_transaction_by_block_hash_and_index(params: &Value, server: &Arc<RpcServer>) -> Value {
+    let hash = parse_h256(params[0].as_str().unwrap_or(""));
+
+    let blockchain = server.blockchain.lock().unwrap();
+    transaction_in_block_to_json(blockchain.get_block_by_hash(hash), params[1].as_str().unwrap_or(""))
+}
+
+fn transaction_in_block_to_json(block: Option<&Block>, index_str: &str) -> Value {
+    let index = parse_u64(index_str) as usize;
+    match block.and_then(|block| block.transactions.get(index).map(|tx| (tx, block))) {
+        Some((tx, block)) => transaction_to_json(tx, Some((block, index))),
+        None => json!(null),
+    }
+}
+
+/// Formats a transaction the way `eth_getTransactionByHash` returns it. `location` is the
+/// containing block and index, or `None` for transactions that are not mined yet.
+fn transaction_to_json(tx: &Transaction, location: Option<(&Block, usize)>) -> Value {
+    let mut value = json!({
+        "hash": format!("0x{:x}", tx.hash.unwrap_or_else(|| tx.calculate_hash())),
+        "nonce": format!("0x{:x}", tx.nonce),
+        "blockHash": location.map(|(block, _)| format!("0x{:x}", block.hash.unwrap_or(H256::zero()))),
+        "blockNumber": location.map(|(block, _)| format!("0x{:x}", block.number)),
+        "transactionIndex": location.map(|(_, index)| format!("0x{:x}", index)),
+        "from": format!("0x{}", hex::encode(tx.from.as_bytes())),
+        "to": tx.to.map(|addr| format!("0x{}", hex::encode(addr.as_bytes()))),
+        "value": format!("0x{:x}", tx.value),
+        "gas": format!("0x{:x}", tx.gas_limit),
+        "gasPrice": format!("0x{:x}", tx.gas_price),
+        "input": format!("0x{}", hex::encode(&tx.data)),
+        "type": format!("0x{:x}", tx.envelope_type.type_byte()),
+        "v": format!("0x{:x}", tx.v),
+        "r": format!("0x{:x}", tx.r),
+        "s": format!("0x{:x}", tx.s)
+    });
+
+    if let Some(chain_id) = tx.chain_id {
+        value["chainId"] = json!(format!("0x{:x}", chain_id));
+    }
+    if let (Some(max_fee), Some(max_priority_fee)) = (tx.max_fee_per_gas, tx.max_priority_fee_per_gas) {
+        value["maxFeePerGas"] = json!(format!("0x{:x}", max_fee));
+        value["maxPriorityFeePerGas"] = json!(format!("0x{:x}", max_priority_fee));
+    }
+    if tx.envelope_type != EnvelopeType::Legacy {
+        value["accessList"] = json!(tx.access_list.iter().map(|item| json!({
+            "address": format!("0x{}", hex::encode(item.address.as_bytes())),
+            "storageKeys": item.storage_keys.iter().map(|key| format!("0x{:x}", key)).collect::<Vec<_>>()
+        })).collect::<Vec<_>>());
+    }
+
+    value
+}
+
 fn handle_get_transaction_receipt(params: &Value, server: &Arc<RpcServer>) -> Value {
-    json!(null)
+    let hash = parse_h256(params[0].as_str().unwrap_or(""));
+
+    let blockchain = server.blockchain.lock().unwrap();
+    match blockchain.get_transaction_receipt(&hash) {
+        Some(receipt) => receipt_to_json(receipt),
+        None => json!(null),
+    }
 }
 
-fn handle_eth_accounts() -> Value {
-    json!([
-        "0x1111111111111111111111111111111111111111",
-        "0x6464646464646464646464646464646464646464"
-    ])
+fn receipt_to_json(receipt: &TransactionReceipt) -> Value {
+    let logs = receipt.logs.iter().map(|log| {
+        json!({
+            "address": format!("0x{}", hex::encode(log.address.as_bytes())),
+            "topics": log.topics.iter().map(|topic| format!("0x{:x}", topic)).collect::<Vec<_>>(),
+            "data": format!("0x{}", hex::encode(&log.data)),
+            "blockHash": format!("0x{:x}", receipt.block_hash),
+            "blockNumber": format!("0x{:x}", receipt.block_number),
+            "transactionHash": format!("0x{:x}", receipt.transaction_hash),
+            "transactionIndex": format!("0x{:x}", receipt.transaction_index),
+            "logIndex": format!("0x{:x}", log.log_index),
+            "removed": false
+        })
+    }).collect::<Vec<_>>();
+
+    let mut value = json!({
+        "transactionHash": format!("0x{:x}", receipt.transaction_hash),
+        "transactionIndex": format!("0x{:x}", receipt.transaction_index),
+        "blockHash": format!("0x{:x}", receipt.block_hash),
+        "blockNumber": format!("0x{:x}", receipt.block_number),
+        "from": format!("0x{}", hex::encode(receipt.from.as_bytes())),
+        "to": receipt.to.map(|addr| format!("0x{}", hex::encode(addr.as_bytes()))),
+        "cumulativeGasUsed": format!("0x{:x}", receipt.cumulative_gas_used),
+        "gasUsed": format!("0x{:x}", receipt.gas_used),
+        "effectiveGasPrice": format!("0x{:x}", receipt.effective_gas_price),
+        "contractAddress": receipt.contract_address.map(|addr| format!("0x{}", hex::encode(addr.as_bytes()))),
+        "logs": logs,
+        "logsBloom": format!("0x{}", hex::encode(receipt.logs_bloom.as_bytes())),
+        "status": if receipt.status { "0x1" } else { "0x0" },
+        "type": format!("0x{:x}", receipt.envelope_type.type_byte())
+    });
+    if let Some(reason) = &receipt.revert_reason {
+        value["revertReason"] = json!(reason);
+    }
+
+    value
 }
 
-async fn mine_pending_transactions(server: &Arc<RpcServer>) {
-    let transactions = {
-        let mut pending = server.pending_transactions.lock().unwrap();
-        let txs = pending.clone();
-        pending.clear();
-        txs
-    };
+/// Otterscan's view of what contract code did within a mined transaction: value transfers,
+/// self-destructs and creations, each with its numeric `type`.
+fn handle_get_internal_operations(params: &Value, server: &Arc<RpcServer>) -> RpcResult {
+    let hash = parse_h256(params[0].as_str().unwrap_or(""));
 
-    if !transactions.is_empty() {
-        println!("Auto-mining {} pending transactions...", transactions.len());
+    let blockchain = server.blockchain.lock().unwrap();
+    let operations = blockchain.get_internal_operations(&hash)
+        .ok_or_else(|| RpcError::new(-32000, format!("Transaction {:?} not found", hash)))?;
 
-        let result = {
-            let mut blockchain = server.blockchain.lock().unwrap();
-            server.miner.mine_block(&mut blockchain, transactions, 2)
-        };
+    Ok(json!(operations.iter().map(|operation| json!({
+        "type": operation.kind as u8,
+        "from": format!("0x{}", hex::encode(operation.from.as_bytes())),
+        "to": format!("0x{}", hex::encode(operation.to.as_bytes())),
+        "value": format!("0x{:x}", operation.value)
+    })).collect::<Vec<_>>()))
+}
+
+fn handle_eth_accounts(server: &Arc<RpcServer>) -> Value {
+    let mut accounts: Vec<_> = server.unlocked_accounts.keys()
+        .map(|address| format!("0x{}", hex::encode(address.as_bytes())))
+        .collect();
+    accounts.sort();
+    json!(accounts)
+}
+
+async fn mine_pending_transactions(server: &Arc<RpcServer>) {
+    // Same lock order as `submit_transaction`: blockchain, then pool.
+    let mut blockchain = server.blockchain.lock().unwrap();
+    let mut pending = server.pending_transactions.lock().unwrap();
+
+    if !pending.is_empty() {
+        println!("Auto-mining {} pending transactions...", pending.len());
 
-        match result {
-            Ok(_) => println!("Block mined successfully!"),
-            Err(e) => println!("Mining failed: {}", e),
+        // The pool is only drained once the block is in, so a failed attempt loses nothing.
+        match server.miner.mine_block(&mut blockchain, pending.clone(), 2) {
+            Ok(_) => {
+                pending.clear();
+                println!("Block mined successfully!");
+            }
+            Err(e) => println!("Mining failed, {} transactions stay pending: {}", pending.len(), e),
         }
     }
 }
 
 // Helper functions
+
+fn parse_call_request(call_params: &Value) -> CallRequest {
+    CallRequest {
+        from: call_params["from"].as_str().map(parse_address),
+        to: call_params["to"].as_str().map(parse_address),
+        value: call_params["value"].as_str().map(parse_u256).unwrap_or_default(),
+        // `input` is the newer name for `data`; prefer it when both are present.
+        data: call_params["input"].as_str().or(call_params["data"].as_str())
+            .map(parse_hex_data)
+            .unwrap_or_default(),
+        gas: call_params["gas"].as_str().map(parse_u64),
+        gas_price: call_params["gasPrice"].as_str().map(parse_u256),
+        nonce: call_params["nonce"].as_str().map(parse_u64),
+    }
+}
+
+/// Parses geth's state override set (address to account fields) and block overrides. Either
+/// may be missing or null.
+fn parse_call_overrides(state: &Value, block: &Value) -> Result<CallOverrides, RpcError> {
+    let mut accounts = HashMap::new();
+    for (address, fields) in state.as_object().into_iter().flatten() {
+        if !fields["state"].is_null() && !fields["stateDiff"].is_null() {
+            return Err(RpcError::new(-32602, format!("account {} has both 'state' and 'stateDiff'", address)));
+        }
+
+        let parse_slots = |slots: &Value| slots.as_object()
+            .map(|slots| slots.iter()
+                .map(|(key, value)| (parse_u256(key), parse_u256(value.as_str().unwrap_or_default())))
+                .collect::<HashMap<_, _>>());
+        accounts.insert(parse_address(address), AccountOverride {
+            balance: fields["balance"].as_str().map(parse_u256),
+            nonce: fields["nonce"].as_str().map(parse_u64),
+            code: fields["code"].as_str().map(parse_hex_data),
+            state: parse_slots(&fields["state"]),
+            state_diff: parse_slots(&fields["stateDiff"]).unwrap_or_default(),
+        });
+    }
+
+    Ok(CallOverrides {
+        accounts,
+        // Newer geth names `coinbase` and `baseFee` `feeRecipient` and `baseFeePerGas`.
+        block: BlockOverrides {
+            number: block["number"].as_str().map(parse_u64),
+            time: block["time"].as_str().map(parse_u64),
+            coinbase: block["feeRecipient"].as_str().or(block["coinbase"].as_str()).map(parse_address),
+            base_fee: block["baseFeePerGas"].as_str().or(block["baseFee"].as_str()).map(parse_u256),
+        },
+    })
+}
+
+/// Resolves a block parameter to a block number. Every tag but `earliest` maps to the head:
+/// blocks are final as soon as they are mined and there is no separate pending block.
+fn parse_block_number(tag: &str, blockchain: &Blockchain) -> Result<u64, RpcError> {
+    match tag {
+        "earliest" => Ok(0),
+        "latest" | "pending" | "safe" | "finalized" => Ok(blockchain.get_latest_block().number),
+        number => {
+            let digits = number.strip_prefix("0x")
+                .ok_or_else(|| RpcError::new(-32602, format!("invalid block number: {}", number)))?;
+            u64::from_str_radix(digits, 16)
+                .map_err(|_| RpcError::new(-32602, format!("invalid block number: {}", number)))
+        }
+    }
+}
+
 fn parse_address(addr_str: &str) -> Address {
     let addr_str = addr_str.trim_start_matches("0x");
     if addr_str.len() == 40 {
@@ -297,6 +1050,15 @@ fn parse_address(addr_str: &str) -> Address {
     }
 }
 
+fn parse_h256(hash_str: &str) -> H256 {
+    let hash_str = hash_str.trim_start_matches("0x");
+    if hash_str.len() == 64 {
+        H256::from_slice(&hex::decode(hash_str).unwrap_or_else(|_| vec![0u8; 32]))
+    } else {
+        H256::zero()
+    }
+}
+
 fn parse_u256(value_str: &str) -> U256 {
     let value_str = value_str.trim_start_matches("0x");
     U256::from_str_radix(value_str, 16).unwrap_or(U256::zero())
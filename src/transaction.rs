@@ -1,4 +1,6 @@
 use ethereum_types::{Address, U256, H256};
+use k256::ecdsa::{RecoveryId, Signature, SigningKey, VerifyingKey};
+use rlp::{Decodable, Rlp, RlpStream};
 use serde::{Deserialize, Serialize};
 use sha3::{Digest, Keccak256};
 
@@ -9,6 +11,42 @@ pub enum TransactionType {
     ContractCall,
 }
 
+impl TransactionType {
+    /// Derives the kind of a transaction from what it does rather than what the sender claims:
+    /// anything that reaches code or carries calldata is a contract call.
+    pub fn classify(to: Option<Address>, data: &[u8], target_has_code: bool) -> Self {
+        match to {
+            None => TransactionType::ContractDeployment,
+            Some(_) if target_has_code || !data.is_empty() => TransactionType::ContractCall,
+            Some(_) => TransactionType::Transfer,
+        }
+    }
+}
+
+/// EIP-2718 envelope the transaction was signed under.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EnvelopeType {
+    Legacy,
+    AccessList,
+    DynamicFee,
+}
+
+impl EnvelopeType {
+    pub fn type_byte(&self) -> u8 {
+        match self {
+            EnvelopeType::Legacy => 0x00,
+            EnvelopeType::AccessList => 0x01,
+            EnvelopeType::DynamicFee => 0x02,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AccessListItem {
+    pub address: Address,
+    pub storage_keys: Vec<H256>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Transaction {
     pub from: Address,
@@ -16,55 +54,62 @@ pub struct Transaction {
     pub value: U256,
     pub data: Vec<u8>,
     pub gas_limit: u64,
+    /// Legacy gas price; for dynamic fee transactions this mirrors `max_fee_per_gas`.
     pub gas_price: U256,
     pub nonce: u64,
     pub hash: Option<H256>,
     pub tx_type: TransactionType,
+    pub envelope_type: EnvelopeType,
+    pub chain_id: Option<u64>,
+    pub max_fee_per_gas: Option<U256>,
+    pub max_priority_fee_per_gas: Option<U256>,
+    pub access_list: Vec<AccessListItem>,
+    pub v: u64,
+    pub r: U256,
+    pub s: U256,
 }
 
 impl Transaction {
     pub fn new_transfer(from: Address, to: Address, value: U256, nonce: u64) -> Self {
-        Transaction {
+        Self::new_with_gas(
             from,
-            to: Some(to),
+            Some(to),
             value,
-            data: Vec::new(),
-            gas_limit: 21000,
-            gas_price: U256::from(20_000_000_000u64),
+            Vec::new(),
+            21000,
+            U256::from(20_000_000_000u64),
             nonce,
-            hash: None,
-            tx_type: TransactionType::Transfer,
-        }
+            TransactionType::Transfer,
+        )
     }
 
     pub fn new_contract_deployment(from: Address, bytecode: Vec<u8>, value: U256, nonce: u64) -> Self {
-        Transaction {
+        Self::new_with_gas(
             from,
-            to: None,
+            None,
             value,
-            data: bytecode,
-            gas_limit: 2_000_000,
-            gas_price: U256::from(20_000_000_000u64),
+            bytecode,
+            2_000_000,
+            U256::from(20_000_000_000u64),
             nonce,
-            hash: None,
-            tx_type: TransactionType::ContractDeployment,
-        }
+            TransactionType::ContractDeployment,
+        )
     }
 
     pub fn new_contract_call(from: Address, to: Address, calldata: Vec<u8>, value: U256, nonce: u64) -> Self {
-        Transaction {
+        Self::new_with_gas(
             from,
-            to: Some(to),
+            Some(to),
             value,
-            data: calldata,
-            gas_limit: 500_000,
-            gas_price: U256::from(20_000_000_000u64),
+            calldata,
+            500_000,
+            U256::from(20_000_000_000u64),
             nonce,
-            hash: None,
-            tx_type: TransactionType::ContractCall,
-        }
+            TransactionType::ContractCall,
+        )
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn new_with_gas(
         from: Address,
         to: Option<Address>,
@@ -85,45 +130,270 @@ impl Transaction {
             nonce,
             hash: None,
             tx_type,
+            envelope_type: EnvelopeType::Legacy,
+            chain_id: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            access_list: Vec::new(),
+            v: 0,
+            r: U256::zero(),
+            s: U256::zero(),
         }
     }
 
-    pub fn calculate_hash(&self) -> H256 {
-        let mut hasher = Keccak256::new();
-        hasher.update(self.from.as_bytes());
+    /// Turns this into an EIP-1559 transaction with the given fee caps; `gas_price` mirrors the
+    /// max fee so balance checks cover the worst case.
+    pub fn set_dynamic_fee(&mut self, max_fee_per_gas: U256, max_priority_fee_per_gas: U256) {
+        self.envelope_type = EnvelopeType::DynamicFee;
+        self.max_fee_per_gas = Some(max_fee_per_gas);
+        self.max_priority_fee_per_gas = Some(max_priority_fee_per_gas);
+        self.gas_price = max_fee_per_gas;
+    }
 
-        if let Some(to) = self.to {
-            hasher.update(to.as_bytes());
-        } else {
-            hasher.update(&[0u8; 20]);
+    /// Decodes a signed EIP-2718 payload (legacy, EIP-2930 or EIP-1559) and recovers its sender.
+    /// Only canonical encodings are accepted, so the hash always equals keccak of `raw`.
+    pub fn decode(raw: &[u8]) -> Result<Self, String> {
+        let first = *raw.first().ok_or_else(|| "Empty transaction payload".to_string())?;
+
+        let mut tx = match first {
+            0xc0..=0xff => Self::decode_legacy(&Self::rlp_list(raw, 9)?)?,
+            0x01 => Self::decode_typed(&Self::rlp_list(&raw[1..], 11)?, EnvelopeType::AccessList)?,
+            0x02 => Self::decode_typed(&Self::rlp_list(&raw[1..], 12)?, EnvelopeType::DynamicFee)?,
+            other => return Err(format!("Unsupported transaction type 0x{:02x}", other)),
+        };
+
+        tx.tx_type = TransactionType::classify(tx.to, &tx.data, false);
+
+        if tx.encode() != raw {
+            return Err("Non-canonical transaction encoding".to_string());
         }
 
-        let mut value_bytes = [0u8; 32];
-        self.value.to_big_endian(&mut value_bytes);
-        hasher.update(&value_bytes);
+        tx.from = tx.recover_sender(tx.chain_id.unwrap_or_default())?;
+        tx.set_hash();
+        Ok(tx)
+    }
 
-        hasher.update(&self.data);
-        hasher.update(&self.gas_limit.to_be_bytes());
+    fn rlp_list(payload: &[u8], expected_items: usize) -> Result<Rlp<'_>, String> {
+        let rlp = Rlp::new(payload);
+        let info = rlp.payload_info().map_err(|e| format!("Invalid RLP: {}", e))?;
+        if !rlp.is_list() || info.header_len + info.value_len != payload.len() {
+            return Err("Invalid RLP: expected a single list".to_string());
+        }
 
-        let mut gas_price_bytes = [0u8; 32];
-        self.gas_price.to_big_endian(&mut gas_price_bytes);
-        hasher.update(&gas_price_bytes);
+        let count = rlp.item_count().map_err(|e| format!("Invalid RLP: {}", e))?;
+        if count != expected_items {
+            return Err(format!("Invalid RLP: expected {} fields, got {}", expected_items, count));
+        }
+        Ok(rlp)
+    }
 
-        hasher.update(&self.nonce.to_be_bytes());
+    fn decode_legacy(rlp: &Rlp) -> Result<Self, String> {
+        let mut tx = Self::new_with_gas(
+            Address::zero(),
+            decode_to(rlp, 3)?,
+            decode_field(rlp, 4)?,
+            decode_field(rlp, 5)?,
+            decode_field(rlp, 2)?,
+            decode_field(rlp, 1)?,
+            decode_field(rlp, 0)?,
+            TransactionType::Transfer,
+        );
+
+        tx.v = decode_field(rlp, 6)?;
+        tx.r = decode_field(rlp, 7)?;
+        tx.s = decode_field(rlp, 8)?;
+        tx.chain_id = if tx.v >= 35 { Some((tx.v - 35) / 2) } else { None };
+        Ok(tx)
+    }
+
+    fn decode_typed(rlp: &Rlp, envelope_type: EnvelopeType) -> Result<Self, String> {
+        // EIP-1559 replaces the single gas price with a tip cap and a fee cap.
+        let fee_fields = if envelope_type == EnvelopeType::DynamicFee { 2 } else { 1 };
+        let gas_price: U256 = decode_field(rlp, 1 + fee_fields)?;
+
+        let mut tx = Self::new_with_gas(
+            Address::zero(),
+            decode_to(rlp, 3 + fee_fields)?,
+            decode_field(rlp, 4 + fee_fields)?,
+            decode_field(rlp, 5 + fee_fields)?,
+            decode_field(rlp, 2 + fee_fields)?,
+            gas_price,
+            decode_field(rlp, 1)?,
+            TransactionType::Transfer,
+        );
+
+        tx.envelope_type = envelope_type;
+        tx.chain_id = Some(decode_field(rlp, 0)?);
+        if envelope_type == EnvelopeType::DynamicFee {
+            tx.max_priority_fee_per_gas = Some(decode_field(rlp, 2)?);
+            tx.max_fee_per_gas = Some(gas_price);
+        }
+
+        let access_list = rlp.at(6 + fee_fields).map_err(|e| format!("Invalid RLP: {}", e))?;
+        for item in access_list.iter() {
+            tx.access_list.push(AccessListItem {
+                address: decode_field(&item, 0)?,
+                storage_keys: item.list_at(1).map_err(|e| format!("Invalid access list: {}", e))?,
+            });
+        }
+
+        tx.v = decode_field(rlp, 7 + fee_fields)?;
+        tx.r = decode_field(rlp, 8 + fee_fields)?;
+        tx.s = decode_field(rlp, 9 + fee_fields)?;
+        Ok(tx)
+    }
 
-        hasher.update(&[match self.tx_type {
-            TransactionType::Transfer => 0,
-            TransactionType::ContractDeployment => 1,
-            TransactionType::ContractCall => 2,
-        }]);
+    /// Canonical signed encoding: `rlp(fields)` for legacy transactions and
+    /// `type || rlp(fields)` for EIP-2718 typed ones.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut stream = RlpStream::new_list(self.payload_field_count() + 3);
+        self.append_payload(&mut stream);
+        stream.append(&self.v);
+        stream.append(&self.r);
+        stream.append(&self.s);
+        self.with_type_prefix(&stream.out())
+    }
 
-        H256::from_slice(&hasher.finalize())
+    pub fn calculate_hash(&self) -> H256 {
+        H256::from_slice(&Keccak256::digest(self.encode()))
     }
 
     pub fn set_hash(&mut self) {
         self.hash = Some(self.calculate_hash());
     }
 
+    /// Hash the sender signs. Legacy transactions use EIP-155 when a chain id is set;
+    /// typed transactions sign `type || rlp(fields)`.
+    pub fn signing_hash(&self) -> H256 {
+        let eip155 = self.envelope_type == EnvelopeType::Legacy && self.chain_id.is_some();
+
+        let mut stream = RlpStream::new_list(self.payload_field_count() + if eip155 { 3 } else { 0 });
+        self.append_payload(&mut stream);
+        if eip155 {
+            stream.append(&self.chain_id.unwrap_or_default());
+            stream.append_empty_data();
+            stream.append_empty_data();
+        }
+
+        H256::from_slice(&Keccak256::digest(self.with_type_prefix(&stream.out())))
+    }
+
+    fn payload_field_count(&self) -> usize {
+        match self.envelope_type {
+            EnvelopeType::Legacy => 6,
+            EnvelopeType::AccessList => 8,
+            EnvelopeType::DynamicFee => 9,
+        }
+    }
+
+    /// Appends every field except the signature, in the envelope's canonical order.
+    fn append_payload(&self, stream: &mut RlpStream) {
+        if self.envelope_type != EnvelopeType::Legacy {
+            stream.append(&self.chain_id.unwrap_or_default());
+        }
+
+        stream.append(&self.nonce);
+        if self.envelope_type == EnvelopeType::DynamicFee {
+            stream.append(&self.max_priority_fee_per_gas.unwrap_or_default());
+            stream.append(&self.max_fee_per_gas.unwrap_or(self.gas_price));
+        } else {
+            stream.append(&self.gas_price);
+        }
+        stream.append(&self.gas_limit);
+        self.append_to(stream);
+        stream.append(&self.value);
+        stream.append(&self.data);
+
+        if self.envelope_type != EnvelopeType::Legacy {
+            self.append_access_list(stream);
+        }
+    }
+
+    fn with_type_prefix(&self, rlp: &[u8]) -> Vec<u8> {
+        match self.envelope_type {
+            EnvelopeType::Legacy => rlp.to_vec(),
+            envelope_type => [&[envelope_type.type_byte()], rlp].concat(),
+        }
+    }
+
+    fn append_to(&self, stream: &mut RlpStream) {
+        match self.to {
+            Some(to) => stream.append(&to),
+            None => stream.append_empty_data(),
+        };
+    }
+
+    fn append_access_list(&self, stream: &mut RlpStream) {
+        stream.begin_list(self.access_list.len());
+        for item in &self.access_list {
+            stream.begin_list(2);
+            stream.append(&item.address);
+            stream.append_list(&item.storage_keys);
+        }
+    }
+
+    /// Signs with `secret_key` for `chain_id` and refreshes the hash. Legacy transactions
+    /// get an EIP-155 `v`; typed transactions store the bare y-parity.
+    pub fn sign(&mut self, secret_key: &[u8], chain_id: u64) -> Result<(), String> {
+        let signing_key = SigningKey::from_slice(secret_key)
+            .map_err(|e| format!("Invalid private key: {}", e))?;
+
+        self.chain_id = Some(chain_id);
+        let (signature, recovery_id) = signing_key
+            .sign_prehash_recoverable(self.signing_hash().as_bytes())
+            .map_err(|e| format!("Signing failed: {}", e))?;
+
+        self.r = U256::from_big_endian(&signature.r().to_bytes());
+        self.s = U256::from_big_endian(&signature.s().to_bytes());
+        self.v = match self.envelope_type {
+            EnvelopeType::Legacy => recovery_id.to_byte() as u64 + chain_id * 2 + 35,
+            _ => recovery_id.to_byte() as u64,
+        };
+        self.set_hash();
+        Ok(())
+    }
+
+    pub fn is_signed(&self) -> bool {
+        !self.r.is_zero() && !self.s.is_zero()
+    }
+
+    /// Recovers the signer, rejecting signatures made for a different chain.
+    pub fn recover_sender(&self, chain_id: u64) -> Result<Address, String> {
+        if !self.is_signed() {
+            return Err("Transaction is not signed".to_string());
+        }
+
+        if let Some(signed_chain_id) = self.chain_id
+            && signed_chain_id != chain_id
+        {
+            return Err(format!("Invalid chain id. Expected {}, got {}", chain_id, signed_chain_id));
+        }
+
+        let recovery_byte = match (self.envelope_type, self.v) {
+            (EnvelopeType::Legacy, 27 | 28) if self.chain_id.is_none() => (self.v - 27) as u8,
+            (EnvelopeType::Legacy, v) if v >= 35 && self.chain_id == Some((v - 35) / 2) => ((v - 35) % 2) as u8,
+            (EnvelopeType::AccessList | EnvelopeType::DynamicFee, 0 | 1) => self.v as u8,
+            (_, v) => return Err(format!("Invalid signature v value: {}", v)),
+        };
+
+        let mut signature_bytes = [0u8; 64];
+        self.r.to_big_endian(&mut signature_bytes[..32]);
+        self.s.to_big_endian(&mut signature_bytes[32..]);
+
+        let signature = Signature::from_slice(&signature_bytes)
+            .map_err(|e| format!("Invalid signature: {}", e))?;
+        let recovery_id = RecoveryId::from_byte(recovery_byte)
+            .ok_or_else(|| "Invalid recovery id".to_string())?;
+
+        let verifying_key = VerifyingKey::recover_from_prehash(
+            self.signing_hash().as_bytes(),
+            &signature,
+            recovery_id,
+        ).map_err(|e| format!("Signature recovery failed: {}", e))?;
+
+        Ok(public_key_to_address(&verifying_key))
+    }
+
     pub fn is_contract_deployment(&self) -> bool {
         matches!(self.tx_type, TransactionType::ContractDeployment)
     }
@@ -136,6 +406,15 @@ impl Transaction {
         matches!(self.tx_type, TransactionType::Transfer)
     }
 
+    /// Price per gas actually paid given the block's base fee; legacy and access-list
+    /// transactions always pay their `gas_price`.
+    pub fn effective_gas_price(&self, base_fee: U256) -> U256 {
+        match (self.max_fee_per_gas, self.max_priority_fee_per_gas) {
+            (Some(max_fee), Some(max_priority_fee)) => max_fee.min(base_fee + max_priority_fee),
+            _ => self.gas_price,
+        }
+    }
+
     pub fn estimated_gas_cost(&self) -> U256 {
         self.gas_price * U256::from(self.gas_limit)
     }
@@ -149,6 +428,12 @@ impl Transaction {
             return Err("Gas price cannot be zero".to_string());
         }
 
+        if let (Some(max_fee), Some(max_priority_fee)) = (self.max_fee_per_gas, self.max_priority_fee_per_gas)
+            && max_priority_fee > max_fee
+        {
+            return Err("Max priority fee per gas exceeds max fee per gas".to_string());
+        }
+
         match self.tx_type {
             TransactionType::Transfer => {
                 if self.to.is_none() {
@@ -201,19 +486,37 @@ impl Transaction {
         }
     }
 
+    /// Size of the signed encoding in bytes.
     pub fn size(&self) -> usize {
-        20 + // from
-            20 + // to (even if None, we count it)
-            32 + // value
-            self.data.len() + // data
-            8 + // gas_limit
-            32 + // gas_price
-            8 + // nonce
-            32 + // hash
-            1 // tx_type
+        self.encode().len()
+    }
+}
+
+fn decode_field<T: Decodable>(rlp: &Rlp, index: usize) -> Result<T, String> {
+    rlp.val_at(index).map_err(|e| format!("Invalid RLP field {}: {}", index, e))
+}
+
+fn decode_to(rlp: &Rlp, index: usize) -> Result<Option<Address>, String> {
+    let item = rlp.at(index).map_err(|e| format!("Invalid RLP field {}: {}", index, e))?;
+    if item.is_empty() {
+        Ok(None)
+    } else {
+        item.as_val().map(Some).map_err(|e| format!("Invalid recipient: {}", e))
     }
 }
 
+pub fn public_key_to_address(key: &VerifyingKey) -> Address {
+    let encoded = key.to_encoded_point(false);
+    let hash = Keccak256::digest(&encoded.as_bytes()[1..]);
+    Address::from_slice(&hash[12..])
+}
+
+pub fn address_from_secret_key(secret_key: &[u8]) -> Result<Address, String> {
+    let signing_key = SigningKey::from_slice(secret_key)
+        .map_err(|e| format!("Invalid private key: {}", e))?;
+    Ok(public_key_to_address(signing_key.verifying_key()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -294,6 +597,14 @@ mod tests {
             nonce: 0,
             hash: None,
             tx_type: TransactionType::Transfer,
+            envelope_type: EnvelopeType::Legacy,
+            chain_id: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            access_list: Vec::new(),
+            v: 0,
+            r: U256::zero(),
+            s: U256::zero(),
         };
         assert!(invalid_transfer.validate().is_err());
 
@@ -307,6 +618,14 @@ mod tests {
             nonce: 0,
             hash: None,
             tx_type: TransactionType::ContractDeployment,
+            envelope_type: EnvelopeType::Legacy,
+            chain_id: None,
+            max_fee_per_gas: None,
+            max_priority_fee_per_gas: None,
+            access_list: Vec::new(),
+            v: 0,
+            r: U256::zero(),
+            s: U256::zero(),
         };
         assert!(invalid_deployment.validate().is_err());
     }
@@ -320,4 +639,132 @@ mod tests {
         let expected_cost = U256::from(20_000_000_000u64) * U256::from(21000);
         assert_eq!(tx.estimated_gas_cost(), expected_cost);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_sign_and_recover_sender() {
+        // First Hardhat/Anvil development key.
+        let secret = hex::decode("ac0974bec39a17e36ba4a6b4d238ff944bacb478cbed5efcae784d7bf4f2ff80").unwrap();
+        let from = address_from_secret_key(&secret).unwrap();
+        assert_eq!(format!("{:x}", from), "f39fd6e51aad88f6f4ce6ab8827279cfffb92266");
+
+        let mut tx = Transaction::new_transfer(from, Address::from([2u8; 20]), U256::from(1000), 0);
+        tx.sign(&secret, 1337).unwrap();
+
+        assert!(tx.is_signed());
+        assert!(tx.v == 2709 || tx.v == 2710);
+        assert_eq!(tx.recover_sender(1337).unwrap(), from);
+        assert!(tx.recover_sender(1).unwrap_err().contains("Invalid chain id"));
+    }
+
+    #[test]
+    fn test_tampered_transaction_recovers_other_sender() {
+        let secret = [7u8; 32];
+        let from = address_from_secret_key(&secret).unwrap();
+
+        let mut tx = Transaction::new_transfer(from, Address::from([2u8; 20]), U256::from(1000), 0);
+        tx.sign(&secret, 1337).unwrap();
+        tx.value = U256::from(1_000_000);
+
+        assert_ne!(tx.recover_sender(1337).unwrap(), from);
+    }
+
+    #[test]
+    fn test_unsigned_transaction_has_no_sender() {
+        let tx = Transaction::new_transfer(Address::from([1u8; 20]), Address::from([2u8; 20]), U256::from(1), 0);
+        assert!(!tx.is_signed());
+        assert!(tx.recover_sender(1337).is_err());
+    }
+
+    #[test]
+    fn test_decode_eip155_example() {
+        // Example transaction from the EIP-155 specification (chain id 1).
+        let raw = hex::decode("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").unwrap();
+        let tx = Transaction::decode(&raw).unwrap();
+
+        assert_eq!(tx.envelope_type, EnvelopeType::Legacy);
+        assert_eq!(tx.chain_id, Some(1));
+        assert_eq!(tx.nonce, 9);
+        assert_eq!(tx.gas_price, U256::from(20_000_000_000u64));
+        assert_eq!(tx.gas_limit, 21000);
+        assert_eq!(tx.to, Some(Address::from([0x35u8; 20])));
+        assert_eq!(tx.value, U256::from(1_000_000_000_000_000_000u64));
+        assert!(tx.is_transfer());
+        assert_eq!(
+            format!("{:x}", tx.signing_hash()),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+        assert_eq!(format!("{:x}", tx.from), "9d8a62f656a8d1615c1294fd71e9cfb3e4855a4f");
+        assert_eq!(tx.hash, Some(H256::from_slice(&Keccak256::digest(&raw))));
+        assert!(tx.recover_sender(1337).is_err());
+    }
+
+    #[test]
+    fn test_decode_dynamic_fee_transaction() {
+        let secret = [7u8; 32];
+        let from = address_from_secret_key(&secret).unwrap();
+
+        let mut tx = Transaction::new_contract_call(from, Address::from([2u8; 20]), vec![0xa9, 0x05], U256::from(5), 3);
+        tx.envelope_type = EnvelopeType::DynamicFee;
+        tx.max_priority_fee_per_gas = Some(U256::from(2_000_000_000u64));
+        tx.max_fee_per_gas = Some(U256::from(30_000_000_000u64));
+        tx.gas_price = U256::from(30_000_000_000u64);
+        tx.access_list = vec![AccessListItem {
+            address: Address::from([2u8; 20]),
+            storage_keys: vec![H256::from_low_u64_be(1)],
+        }];
+        tx.sign(&secret, 1337).unwrap();
+
+        let raw = tx.encode();
+        assert_eq!(raw[0], 0x02);
+
+        let decoded = Transaction::decode(&raw).unwrap();
+        assert_eq!(decoded.envelope_type, EnvelopeType::DynamicFee);
+        assert_eq!(decoded.from, from);
+        assert_eq!(decoded.chain_id, Some(1337));
+        assert_eq!(decoded.max_priority_fee_per_gas, tx.max_priority_fee_per_gas);
+        assert_eq!(decoded.gas_price, U256::from(30_000_000_000u64));
+        assert_eq!(decoded.access_list, tx.access_list);
+        assert_eq!(decoded.hash, tx.hash);
+        assert!(decoded.is_contract_call());
+    }
+
+    #[test]
+    fn test_encoding_round_trip() {
+        let raw = hex::decode("f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").unwrap();
+        let tx = Transaction::decode(&raw).unwrap();
+        assert_eq!(tx.encode(), raw);
+        assert_eq!(tx.size(), raw.len());
+        assert_eq!(
+            format!("{:x}", tx.hash.unwrap()),
+            "33469b22e9f636356c4160a87eb19df52b7412e8eac32a4a55ffe88ea8350788"
+        );
+
+        let secret = [7u8; 32];
+        let from = address_from_secret_key(&secret).unwrap();
+        let mut access_list_tx = Transaction::new_contract_deployment(from, vec![0x60, 0x00], U256::zero(), 0);
+        access_list_tx.envelope_type = EnvelopeType::AccessList;
+        access_list_tx.sign(&secret, 1337).unwrap();
+
+        let encoded = access_list_tx.encode();
+        assert_eq!(encoded[0], 0x01);
+        let decoded = Transaction::decode(&encoded).unwrap();
+        assert_eq!(decoded.encode(), encoded);
+        assert_eq!(decoded.from, from);
+        assert!(decoded.is_contract_deployment());
+    }
+
+    #[test]
+    fn test_decode_rejects_non_canonical_encoding() {
+        // Same EIP-155 example with the nonce padded by a leading zero byte.
+        let raw = hex::decode("f86d8200098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83").unwrap();
+        assert!(Transaction::decode(&raw).is_err());
+    }
+
+    #[test]
+    fn test_decode_rejects_malformed_payloads() {
+        assert!(Transaction::decode(&[]).is_err());
+        assert!(Transaction::decode(&[0x05, 0xc0]).is_err());
+        assert!(Transaction::decode(&[0x02, 0xc0]).is_err());
+        assert!(Transaction::decode(&hex::decode("c3010203").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,117 @@
+use ethereum_types::H256;
+use rlp::RlpStream;
+use sha3::{Digest, Keccak256};
+
+/// Root of a trie without entries: keccak(rlp("")).
+pub const EMPTY_TRIE_ROOT: H256 = H256([
+    0x56, 0xe8, 0x1f, 0x17, 0x1b, 0xcc, 0x55, 0xa6, 0xff, 0x83, 0x45, 0xe6, 0x92, 0xc0, 0xf8, 0x6e,
+    0x5b, 0x48, 0xe0, 0x1b, 0x99, 0x6c, 0xad, 0xc0, 0x01, 0x62, 0x2f, 0xb5, 0xe3, 0x63, 0xb4, 0x21,
+]);
+
+/// Merkle Patricia trie root over `values` keyed by rlp(index), as used for the transactions
+/// and receipts roots in block headers.
+pub fn ordered_trie_root<I>(values: I) -> H256
+where
+    I: IntoIterator,
+    I::Item: AsRef<[u8]>,
+{
+    let mut entries: Vec<(Vec<u8>, I::Item)> = values.into_iter()
+        .enumerate()
+        .map(|(index, value)| (nibbles(&rlp::encode(&index)), value))
+        .collect();
+    if entries.is_empty() {
+        return EMPTY_TRIE_ROOT;
+    }
+
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    let entries: Vec<(&[u8], &[u8])> = entries.iter()
+        .map(|(key, value)| (key.as_slice(), value.as_ref()))
+        .collect();
+    H256::from_slice(&Keccak256::digest(encode_node(&entries)))
+}
+
+fn nibbles(bytes: &[u8]) -> Vec<u8> {
+    bytes.iter().flat_map(|byte| [byte >> 4, byte & 0x0f]).collect()
+}
+
+/// RLP of the node holding `entries`, whose keys are the remaining nibbles of each path.
+/// The keys are sorted and, being RLP encodings, none is a prefix of another.
+fn encode_node(entries: &[(&[u8], &[u8])]) -> Vec<u8> {
+    let mut stream = RlpStream::new();
+
+    if let [(key, value)] = entries {
+        stream.begin_list(2);
+        stream.append(&hex_prefix(key, true));
+        stream.append(value);
+        return stream.out().to_vec();
+    }
+
+    // Sorted keys share the longest prefix of the first and the last one.
+    let (first, last) = (entries[0].0, entries[entries.len() - 1].0);
+    let shared = first.iter().zip(last).take_while(|(a, b)| a == b).count();
+    if shared > 0 {
+        let rest: Vec<_> = entries.iter().map(|(key, value)| (&key[shared..], *value)).collect();
+        stream.begin_list(2);
+        stream.append(&hex_prefix(&first[..shared], false));
+        append_child(&mut stream, &encode_node(&rest));
+        return stream.out().to_vec();
+    }
+
+    stream.begin_list(17);
+    for nibble in 0..16u8 {
+        let children: Vec<_> = entries.iter()
+            .filter(|(key, _)| key[0] == nibble)
+            .map(|(key, value)| (&key[1..], *value))
+            .collect();
+        if children.is_empty() {
+            stream.append_empty_data();
+        } else {
+            append_child(&mut stream, &encode_node(&children));
+        }
+    }
+    stream.append_empty_data();
+    stream.out().to_vec()
+}
+
+/// Children shorter than a hash are embedded in their parent, larger ones referenced by hash.
+fn append_child(stream: &mut RlpStream, node: &[u8]) {
+    if node.len() < 32 {
+        stream.append_raw(node, 1);
+    } else {
+        stream.append(&H256::from_slice(&Keccak256::digest(node)));
+    }
+}
+
+/// Compact encoding of a nibble path, flagging leaves and odd lengths in the first nibble.
+fn hex_prefix(path: &[u8], leaf: bool) -> Vec<u8> {
+    let flag = if leaf { 2 } else { 0 } + (path.len() % 2) as u8;
+    let mut encoded = Vec::with_capacity(path.len() / 2 + 1);
+    let rest = if path.len() % 2 == 1 {
+        encoded.push((flag << 4) | path[0]);
+        &path[1..]
+    } else {
+        encoded.push(flag << 4);
+        path
+    };
+    encoded.extend(rest.chunks(2).map(|pair| (pair[0] << 4) | pair[1]));
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_empty_trie_root() {
+        assert_eq!(EMPTY_TRIE_ROOT, H256::from_slice(&Keccak256::digest(rlp::encode(&""))));
+        assert_eq!(ordered_trie_root(Vec::<Vec<u8>>::new()), EMPTY_TRIE_ROOT);
+    }
+
+    #[test]
+    fn test_ordered_trie_root_matches_reference() {
+        // The long value forces a hashed child; the short ones stay embedded.
+        let values = vec![b"hello".to_vec(), vec![0xff; 40], vec![0x01]];
+        let expected = H256::from_slice(&hex::decode("03df6b49eeb68552eae098b87bc5e4bafc7404e62749d72816cd9b5da00b89ac").unwrap());
+        assert_eq!(ordered_trie_root(&values), expected);
+    }
+}